@@ -0,0 +1,231 @@
+// BananaSlice - Aspect Ratio Fitting
+// Supported ratios and how a selection grows to reach the closest one
+
+use serde::{Deserialize, Serialize};
+
+/// Aspect ratios supported by the Gemini API (`imageConfig.aspectRatio`)
+pub const SUPPORTED_ASPECT_RATIOS: &[(u32, u32)] = &[
+    (8, 1),
+    (4, 1),
+    (21, 9),
+    (16, 9),
+    (5, 4),
+    (4, 3),
+    (3, 2),
+    (1, 1),
+    (4, 5),
+    (3, 4),
+    (2, 3),
+    (9, 16),
+    (1, 4),
+    (1, 8),
+];
+
+/// Ratios closer than this to a supported ratio are used as they are
+pub const RATIO_TOLERANCE: f64 = 0.01;
+
+/// The closest of `ratios` to `width`:`height`; ties go to the earlier entry
+pub fn closest_ratio(ratios: &[(u32, u32)], width: u32, height: u32) -> (u32, u32) {
+    let ratio = width as f64 / height as f64;
+    let mut closest = (1, 1);
+    let mut min_diff = f64::MAX;
+    for &(w, h) in ratios {
+        let diff = (ratio - w as f64 / h as f64).abs();
+        if diff < min_diff {
+            min_diff = diff;
+            closest = (w, h);
+        }
+    }
+    closest
+}
+
+/// Display a ratio as `w:h` when it matches a supported ratio, otherwise as `x.xx:1`
+pub fn format_ratio(ratio: f64) -> String {
+    SUPPORTED_ASPECT_RATIOS
+        .iter()
+        .find(|&&(w, h)| (ratio - w as f64 / h as f64).abs() < RATIO_TOLERANCE)
+        .map(|(w, h)| format!("{}:{}", w, h))
+        .unwrap_or_else(|| format!("{:.2}:1", ratio))
+}
+
+/// A selection rectangle in canvas pixels
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct SelectionRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// How a selection must grow to reach the closest supported aspect ratio
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AspectRatioAdjustment {
+    pub original_width: u32,
+    pub original_height: u32,
+    pub adjusted_width: u32,
+    pub adjusted_height: u32,
+    /// Selection ratio, formatted by `format_ratio`
+    pub original_ratio: String,
+    /// Closest supported ratio as `w:h`
+    pub closest_ratio: String,
+    pub needs_adjustment: bool,
+    /// Top-left of the adjusted selection in canvas pixels; negative when it extends past the canvas
+    pub x: f64,
+    pub y: f64,
+    /// How far the adjusted selection extends past each canvas edge. These parts have no
+    /// image to expand into and are padded instead.
+    pub pad_left: u32,
+    pub pad_top: u32,
+    pub pad_right: u32,
+    pub pad_bottom: u32,
+}
+
+/// Place a span of `adjusted` pixels around `start..start + original` along one axis
+///
+/// The growth is centred on the selection and then shifted to stay on the canvas; only
+/// a span wider than the canvas is left hanging over, evenly on both sides.
+/// Returns the new start and the padding before and after the canvas.
+fn place_span(start: f64, original: f64, adjusted: u32, canvas: Option<u32>) -> (f64, u32, u32) {
+    let adjusted_len = adjusted as f64;
+    let centred = start - (adjusted_len - original) / 2.0;
+    let Some(canvas) = canvas.map(f64::from) else {
+        return (centred, 0, 0);
+    };
+    let placed = if adjusted_len <= canvas {
+        centred.clamp(0.0, canvas - adjusted_len)
+    } else {
+        -((adjusted_len - canvas) / 2.0).floor()
+    };
+    let before = (-placed).max(0.0).round() as u32;
+    let after = (placed + adjusted_len - canvas).max(0.0).round() as u32;
+    (placed, before, after)
+}
+
+/// Work out how to grow `selection` to the closest supported aspect ratio
+///
+/// The selection only ever grows: it is widened when the target is wider and made
+/// taller otherwise. With the canvas size given, the growth takes in surrounding
+/// context where the canvas allows and reports the rest as padding.
+pub fn calculate_adjustment(
+    selection: SelectionRect,
+    canvas_width: Option<u32>,
+    canvas_height: Option<u32>,
+) -> Option<AspectRatioAdjustment> {
+    let width = selection.width.round();
+    let height = selection.height.round();
+    if width < 1.0 || height < 1.0 {
+        return None;
+    }
+    let (original_width, original_height) = (width as u32, height as u32);
+    let original_ratio = width / height;
+    let (rw, rh) = closest_ratio(SUPPORTED_ASPECT_RATIOS, original_width, original_height);
+    let target = rw as f64 / rh as f64;
+    let needs_adjustment = (original_ratio - target).abs() > RATIO_TOLERANCE;
+
+    let (mut adjusted_width, mut adjusted_height) = (original_width, original_height);
+    if needs_adjustment {
+        if target > original_ratio {
+            adjusted_width = (height * target).round() as u32;
+        } else {
+            adjusted_height = (width / target).round() as u32;
+        }
+    }
+
+    let (x, pad_left, pad_right) = place_span(selection.x, width, adjusted_width, canvas_width);
+    let (y, pad_top, pad_bottom) = place_span(selection.y, height, adjusted_height, canvas_height);
+
+    Some(AspectRatioAdjustment {
+        original_width,
+        original_height,
+        adjusted_width,
+        adjusted_height,
+        original_ratio: format_ratio(original_ratio),
+        closest_ratio: format!("{}:{}", rw, rh),
+        needs_adjustment,
+        x,
+        y,
+        pad_left,
+        pad_top,
+        pad_right,
+        pad_bottom,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> SelectionRect {
+        SelectionRect { x, y, width, height }
+    }
+
+    #[test]
+    fn exact_ratios_need_no_adjustment() {
+        for &(w, h) in SUPPORTED_ASPECT_RATIOS {
+            let adjustment = calculate_adjustment(rect(0.0, 0.0, (w * 64) as f64, (h * 64) as f64), None, None).unwrap();
+            assert!(!adjustment.needs_adjustment, "{}:{}", w, h);
+            assert_eq!(adjustment.closest_ratio, format!("{}:{}", w, h));
+            assert_eq!(adjustment.original_ratio, adjustment.closest_ratio);
+            assert_eq!((adjustment.adjusted_width, adjustment.adjusted_height), (w * 64, h * 64));
+        }
+    }
+
+    #[test]
+    fn off_ratio_selections_grow_to_each_ratio() {
+        for &(w, h) in SUPPORTED_ASPECT_RATIOS {
+            let target = w as f64 / h as f64;
+            let values = SUPPORTED_ASPECT_RATIOS.iter().map(|&(w, h)| w as f64 / h as f64);
+            let wider = values.clone().filter(|&v| v > target).fold(f64::MAX, f64::min);
+            let narrower = values.filter(|&v| v < target).fold(0.0, f64::max);
+            // Move 30% of the way towards each neighbouring ratio
+            for skewed in [target + (wider.min(target * 2.0) - target) * 0.3, target - (target - narrower) * 0.3] {
+                let height = 800.0;
+                let width = (height * skewed).round();
+                let adjustment = calculate_adjustment(rect(0.0, 0.0, width, height), None, None).unwrap();
+                assert_eq!(adjustment.closest_ratio, format!("{}:{}", w, h), "{} towards {}:{}", skewed, w, h);
+                assert!(adjustment.needs_adjustment);
+                assert!(adjustment.adjusted_width >= adjustment.original_width);
+                assert!(adjustment.adjusted_height >= adjustment.original_height);
+                // Only one side grows
+                assert!(
+                    adjustment.adjusted_width == adjustment.original_width
+                        || adjustment.adjusted_height == adjustment.original_height
+                );
+                let adjusted = adjustment.adjusted_width as f64 / adjustment.adjusted_height as f64;
+                assert!((adjusted - target).abs() < RATIO_TOLERANCE, "{}:{} got {}", w, h, adjusted);
+            }
+        }
+    }
+
+    #[test]
+    fn formats_ratios_like_the_selector() {
+        assert_eq!(format_ratio(16.0 / 9.0), "16:9");
+        assert_eq!(format_ratio(1.9), "1.90:1");
+        assert_eq!(closest_ratio(SUPPORTED_ASPECT_RATIOS, 1000, 10), (8, 1));
+        assert_eq!(closest_ratio(SUPPORTED_ASPECT_RATIOS, 10, 1000), (1, 8));
+    }
+
+    #[test]
+    fn growth_uses_context_then_pads_past_the_canvas() {
+        // 600x500 (6:5) grows to 625x500 (5:4), centred on the selection
+        let adjustment = calculate_adjustment(rect(100.0, 50.0, 600.0, 500.0), Some(1000), Some(1000)).unwrap();
+        assert_eq!((adjustment.adjusted_width, adjustment.adjusted_height), (625, 500));
+        assert_eq!((adjustment.x, adjustment.y), (87.5, 50.0));
+        assert_eq!((adjustment.pad_left, adjustment.pad_right), (0, 0));
+
+        // Against the left edge the growth shifts right instead of leaving the canvas
+        let adjustment = calculate_adjustment(rect(0.0, 0.0, 600.0, 500.0), Some(1000), Some(1000)).unwrap();
+        assert_eq!(adjustment.x, 0.0);
+        assert_eq!((adjustment.pad_left, adjustment.pad_right), (0, 0));
+
+        // A full-height strip cannot grow taller into the canvas, so it is padded
+        let adjustment = calculate_adjustment(rect(0.0, 0.0, 500.0, 600.0), Some(500), Some(600)).unwrap();
+        assert_eq!(adjustment.closest_ratio, "4:5");
+        assert_eq!((adjustment.adjusted_width, adjustment.adjusted_height), (500, 625));
+        assert_eq!((adjustment.pad_top, adjustment.pad_bottom), (12, 13));
+        assert_eq!((adjustment.pad_left, adjustment.pad_right), (0, 0));
+        assert_eq!(adjustment.y, -12.0);
+
+        assert!(calculate_adjustment(rect(0.0, 0.0, 0.0, 10.0), None, None).is_none());
+    }
+}
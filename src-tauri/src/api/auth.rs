@@ -0,0 +1,71 @@
+// Auth Strategies
+// How a provider secret is attached to outgoing requests
+
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+
+fn default_query_param() -> String {
+    "key".to_string()
+}
+
+/// Where the API key or token goes on each request
+///
+/// Headers are preferred; the query-string style exists only for gateways
+/// that require it, since URLs end up in proxy and server logs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "style", rename_all = "snake_case")]
+pub enum AuthStyle {
+    /// `x-goog-api-key: <key>`, the Gemini API default
+    GoogApiKey,
+    /// `Authorization: Bearer <key>`
+    Bearer,
+    /// `<name>: <key>` for gateways with their own header
+    Header { name: String },
+    /// `?<name>=<key>`, opt-in only
+    Query {
+        #[serde(default = "default_query_param")]
+        name: String,
+    },
+}
+
+impl AuthStyle {
+    /// Attach `secret` to a request
+    pub fn apply(&self, builder: RequestBuilder, secret: &str) -> RequestBuilder {
+        match self {
+            AuthStyle::GoogApiKey => builder.header("x-goog-api-key", secret),
+            AuthStyle::Bearer => builder.bearer_auth(secret),
+            AuthStyle::Header { name } => builder.header(name.as_str(), secret),
+            AuthStyle::Query { name } => builder.query(&[(name.as_str(), secret)]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client;
+
+    fn apply(style: AuthStyle) -> reqwest::Request {
+        let builder = Client::new().post("https://example.com/v1beta/models/m:generateContent");
+        style.apply(builder, "secret").build().unwrap()
+    }
+
+    #[test]
+    fn header_styles_keep_the_key_out_of_the_url() {
+        let request = apply(AuthStyle::GoogApiKey);
+        assert_eq!(request.headers()["x-goog-api-key"], "secret");
+        assert_eq!(request.url().query(), None);
+
+        let request = apply(AuthStyle::Bearer);
+        assert_eq!(request.headers()["authorization"], "Bearer secret");
+
+        let request = apply(AuthStyle::Header { name: "api-key".to_string() });
+        assert_eq!(request.headers()["api-key"], "secret");
+    }
+
+    #[test]
+    fn query_style_is_explicit_and_defaults_to_key() {
+        let style: AuthStyle = serde_json::from_str(r#"{"style":"query"}"#).unwrap();
+        assert_eq!(apply(style).url().query(), Some("key=secret"));
+    }
+}
@@ -0,0 +1,308 @@
+// Automatic1111 / Forge API Module
+// Handles communication with a local Stable Diffusion WebUI (/sdapi/v1/img2img)
+
+use super::error::truncate;
+use super::{ApiError, FillRequest, GeneratedImage, GenerationStage, ImageEditProvider, ModelFeedback, ModelInfo, RetryPolicy};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+/// Provider-specific options read from `GenerateRequest.provider_options`
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Automatic1111Options {
+    pub denoising_strength: f32,
+    pub steps: u32,
+    pub seed: i64,
+    pub mask_blur: u32,
+    pub cfg_scale: f32,
+    pub inpaint_full_res: bool,
+    pub inpaint_full_res_padding: u32,
+    pub sampler_name: Option<String>,
+    /// Checkpoint to switch to for this request (`sd_model_checkpoint`)
+    pub checkpoint: Option<String>,
+}
+
+impl Default for Automatic1111Options {
+    fn default() -> Self {
+        Self {
+            denoising_strength: 0.75,
+            steps: 30,
+            seed: -1,
+            mask_blur: 4,
+            cfg_scale: 7.0,
+            inpaint_full_res: true,
+            inpaint_full_res_padding: 32,
+            sampler_name: None,
+            checkpoint: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Img2ImgRequest<'a> {
+    init_images: Vec<&'a str>,
+    mask: &'a str,
+    prompt: &'a str,
+    denoising_strength: f32,
+    steps: u32,
+    seed: i64,
+    mask_blur: u32,
+    cfg_scale: f32,
+    inpaint_full_res: bool,
+    inpaint_full_res_padding: u32,
+    /// 1 = start from the original content under the mask
+    inpainting_fill: u8,
+    /// 0 = inpaint the white areas of the mask
+    inpainting_mask_invert: u8,
+    batch_size: u32,
+    /// Keep the batch grid out of the returned images
+    do_not_save_grid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sampler_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    override_settings: Option<OverrideSettings<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct OverrideSettings<'a> {
+    sd_model_checkpoint: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct Img2ImgResponse {
+    images: Option<Vec<String>>,
+    detail: Option<serde_json::Value>,
+    error: Option<String>,
+}
+
+/// Read image dimensions from base64 data so the output matches the crop
+fn image_dimensions(base64_data: &str) -> Option<(u32, u32)> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    let bytes = STANDARD.decode(base64_data).ok()?;
+    let img = image::load_from_memory(&bytes).ok()?;
+    Some((img.width(), img.height()))
+}
+
+pub struct Automatic1111Client {
+    client: Client,
+    retry: RetryPolicy,
+    base_url: String,
+    options: Automatic1111Options,
+}
+
+impl Automatic1111Client {
+    pub fn new(base_url: Option<String>, options: Automatic1111Options) -> Self {
+        Self {
+            client: Client::new(),
+            retry: RetryPolicy::default(),
+            base_url: base_url.unwrap_or_else(|| "http://127.0.0.1:7860".to_string()),
+            options,
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Use a client built from the network settings (timeouts, proxy, root certificates)
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Check that the WebUI API is reachable by listing its checkpoints
+    pub async fn probe(&self) -> Result<(), ApiError> {
+        let url = format!("{}/sdapi/v1/sd-models", self.base_url.trim_end_matches('/'));
+        let response = self.client.get(&url).send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let response_text = response.text().await?;
+        Err(ApiError::from_status(status, truncate(&response_text, 200).to_string(), None, None))
+    }
+
+    /// Inpaint the masked region with img2img
+    ///
+    /// The mask uses the same white = generate convention as the rest of the
+    /// app, so it is forwarded unchanged.
+    pub async fn generate_fill(&self, request: &FillRequest<'_>) -> Result<Vec<GeneratedImage>, ApiError> {
+        request.progress.stage(GenerationStage::Uploading);
+        let url = format!("{}/sdapi/v1/img2img", self.base_url.trim_end_matches('/'));
+
+        if !request.reference_images.is_empty() {
+            log::warn!("img2img does not support reference images; ignoring {}", request.reference_images.len());
+        }
+
+        let dimensions = image_dimensions(request.image_base64);
+        let body = Img2ImgRequest {
+            init_images: vec![request.image_base64],
+            mask: request.mask_base64,
+            prompt: request.prompt,
+            denoising_strength: self.options.denoising_strength,
+            steps: self.options.steps,
+            seed: self.options.seed,
+            mask_blur: self.options.mask_blur,
+            cfg_scale: self.options.cfg_scale,
+            inpaint_full_res: self.options.inpaint_full_res,
+            inpaint_full_res_padding: self.options.inpaint_full_res_padding,
+            inpainting_fill: 1,
+            inpainting_mask_invert: 0,
+            batch_size: request.candidate_count,
+            do_not_save_grid: true,
+            width: dimensions.map(|(w, _)| w),
+            height: dimensions.map(|(_, h)| h),
+            sampler_name: self.options.sampler_name.as_deref(),
+            override_settings: self
+                .options
+                .checkpoint
+                .as_deref()
+                .map(|checkpoint| OverrideSettings { sd_model_checkpoint: checkpoint }),
+        };
+
+        log::info!("Sending request to img2img: {}", url);
+        request.progress.stage(GenerationStage::Waiting);
+
+        let response = self
+            .retry
+            .send(request.progress, || Ok(self.client.post(&url).json(&body)))
+            .await?;
+
+        let status = response.status();
+        let response_text = response.text().await?;
+
+        log::info!("API response status: {}", status);
+        request.progress.stage(GenerationStage::Decoding);
+
+        // Proxies in front of the WebUI often answer failures with HTML or plain text
+        let img2img_response: Img2ImgResponse = match serde_json::from_str(&response_text) {
+            Ok(parsed) => parsed,
+            Err(_) if !status.is_success() => {
+                return Err(ApiError::from_status(status, truncate(&response_text, 200).to_string(), None, None));
+            }
+            Err(e) => return Err(ApiError::ParseError(format!("{}: {}", e, truncate(&response_text, 200)))),
+        };
+
+        if let Some(detail) = img2img_response.detail {
+            log::error!("img2img error: {}", detail);
+            return Err(ApiError::from_status(status, detail.to_string(), None, None));
+        }
+        if let Some(error) = img2img_response.error {
+            log::error!("img2img error: {}", error);
+            return Err(ApiError::from_status(status, error, None, None));
+        }
+
+        // The WebUI may append extra images (such as the mask) after the batch
+        let images: Vec<GeneratedImage> = img2img_response
+            .images
+            .unwrap_or_default()
+            .into_iter()
+            .take(request.candidate_count as usize)
+            .enumerate()
+            .map(|(index, data)| GeneratedImage::new(index as u32, data))
+            .collect();
+
+        if images.is_empty() {
+            return Err(ApiError::NoImageGenerated(ModelFeedback::default()));
+        }
+        Ok(images)
+    }
+}
+
+#[async_trait]
+impl ImageEditProvider for Automatic1111Client {
+    fn id(&self) -> &'static str {
+        "automatic1111"
+    }
+
+    async fn inpaint(&self, request: &FillRequest<'_>) -> Result<Vec<GeneratedImage>, ApiError> {
+        self.generate_fill(request).await
+    }
+
+    /// The checkpoint comes from the provider options, so `model` is not checked
+    async fn probe(&self, _model: Option<&str>) -> Result<(), ApiError> {
+        Automatic1111Client::probe(self).await
+    }
+
+    /// Nothing to pick: the checkpoint comes from the provider options
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ApiError> {
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_server::{NoProgress, RecordingProgress, StubResponse, StubServer};
+
+    fn fill_request<'a>() -> FillRequest<'a> {
+        FillRequest {
+            model: "",
+            prompt: "a red balloon",
+            image_base64: "aW1hZ2U=",
+            mask_base64: "bWFzaw==",
+            reference_images: &[],
+            candidate_count: 1,
+            progress: &NoProgress,
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_inpainting_payload_and_returns_batch() {
+        let server = StubServer::start(vec![StubResponse::json(200, r#"{"images":["cGF0Y2g=","c2Vjb25k","bWFzaw=="]}"#)]).await;
+        let options = Automatic1111Options { seed: 42, steps: 20, ..Default::default() };
+        let client = Automatic1111Client::new(Some(server.base_url.clone()), options);
+
+        let progress = RecordingProgress::default();
+        let request = FillRequest { candidate_count: 2, progress: &progress, ..fill_request() };
+        let images = client.generate_fill(&request).await.unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].image_base64, "cGF0Y2g=");
+        assert_eq!(images[1].index, 1);
+        assert_eq!(
+            *progress.0.lock().unwrap(),
+            [GenerationStage::Uploading, GenerationStage::Waiting, GenerationStage::Decoding]
+        );
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/sdapi/v1/img2img");
+        assert_eq!(requests[0].headers["content-type"], "application/json");
+
+        let body = requests[0].json();
+        assert_eq!(body["init_images"][0], "aW1hZ2U=");
+        assert_eq!(body["mask"], "bWFzaw==");
+        assert_eq!(body["prompt"], "a red balloon");
+        assert_eq!(body["seed"], 42);
+        assert_eq!(body["steps"], 20);
+        assert_eq!(body["batch_size"], 2);
+        assert_eq!(body["inpaint_full_res"], true);
+        assert!(body.get("override_settings").is_none());
+    }
+
+    #[tokio::test]
+    async fn reports_webui_error_detail() {
+        let server = StubServer::start(vec![StubResponse::json(422, r#"{"detail":"Invalid mask"}"#)]).await;
+        let client = Automatic1111Client::new(Some(server.base_url.clone()), Automatic1111Options::default());
+
+        let result = client.generate_fill(&fill_request()).await;
+        assert!(matches!(result, Err(ApiError::BadRequest { message, .. }) if message.contains("Invalid mask")));
+    }
+
+    #[tokio::test]
+    async fn plain_text_failures_are_classified_by_status() {
+        let server = StubServer::start(vec![StubResponse::bytes(503, "text/html", b"<h1>502 Bad Gateway</h1>".to_vec())]).await;
+        let client = Automatic1111Client::new(Some(server.base_url.clone()), Automatic1111Options::default())
+            .with_retry_policy(RetryPolicy { max_attempts: 1, ..Default::default() });
+
+        let result = client.generate_fill(&fill_request()).await;
+        assert!(matches!(result, Err(ApiError::Service(ref message)) if message.contains("Bad Gateway")), "{:?}", result);
+    }
+}
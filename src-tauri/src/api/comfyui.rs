@@ -0,0 +1,581 @@
+// ComfyUI API Module
+// Runs a user-supplied API-format workflow with templated inputs
+
+use super::error::truncate;
+use super::{
+    ApiError, FillRequest, GeneratedImage, GenerationStage, ImageEditProvider, ModelFeedback, ModelInfo, ProgressSink,
+    RetryPolicy,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::multipart::{Form, Part};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Provider-specific options read from `GenerateRequest.provider_options`
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ComfyUiOptions {
+    /// API-format workflow containing `{{image}}`, `{{mask}}`, `{{prompt}}` and `{{seed}}` placeholders
+    pub workflow: Value,
+    /// Fixed seed; a time-based seed is used when unset
+    pub seed: Option<u64>,
+    /// Only collect images from this node (all output nodes otherwise)
+    pub output_node: Option<String>,
+    pub poll_interval_ms: u64,
+    pub timeout_secs: u64,
+}
+
+impl Default for ComfyUiOptions {
+    fn default() -> Self {
+        Self {
+            workflow: Value::Null,
+            seed: None,
+            output_node: None,
+            poll_interval_ms: 1000,
+            timeout_secs: 300,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadResponse {
+    name: String,
+    #[serde(default)]
+    subfolder: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueueResponse {
+    prompt_id: Option<String>,
+    error: Option<Value>,
+    node_errors: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryEntry {
+    #[serde(default)]
+    outputs: serde_json::Map<String, Value>,
+    status: Option<HistoryStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryStatus {
+    status_str: Option<String>,
+    #[serde(default)]
+    completed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutputImage {
+    filename: String,
+    #[serde(default)]
+    subfolder: String,
+    #[serde(rename = "type", default = "default_output_type")]
+    folder_type: String,
+}
+
+fn default_output_type() -> String {
+    "output".to_string()
+}
+
+/// Values substituted into the workflow template
+struct TemplateValues<'a> {
+    image: &'a str,
+    mask: &'a str,
+    prompt: &'a str,
+    seed: u64,
+}
+
+/// Replace placeholders throughout the workflow. A string that is exactly
+/// `{{seed}}` becomes a number so sampler inputs stay correctly typed.
+fn apply_template(value: &Value, values: &TemplateValues) -> Value {
+    match value {
+        Value::String(s) if s == "{{seed}}" => Value::from(values.seed),
+        Value::String(s) => Value::String(
+            s.replace("{{image}}", values.image)
+                .replace("{{mask}}", values.mask)
+                .replace("{{prompt}}", values.prompt)
+                .replace("{{seed}}", &values.seed.to_string()),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(|item| apply_template(item, values)).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, item)| (key.clone(), apply_template(item, values)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Uploaded images are referenced by "subfolder/name" in LoadImage nodes
+fn uploaded_path(upload: &UploadResponse) -> String {
+    if upload.subfolder.is_empty() {
+        upload.name.clone()
+    } else {
+        format!("{}/{}", upload.subfolder, upload.name)
+    }
+}
+
+fn unique_suffix() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+}
+
+/// A prompt that is still queued or running; dropped unfinished, it is removed
+/// from the queue and interrupted so the server does not keep working on it
+struct QueuedPrompt {
+    client: Client,
+    base_url: String,
+    prompt_id: String,
+    done: bool,
+}
+
+impl QueuedPrompt {
+    fn finish(mut self) {
+        self.done = true;
+    }
+}
+
+impl Drop for QueuedPrompt {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else { return };
+        let client = self.client.clone();
+        let base_url = self.base_url.clone();
+        let prompt_id = self.prompt_id.clone();
+        log::info!("Stopping ComfyUI prompt {}", prompt_id);
+
+        runtime.spawn(async move {
+            // Deleting only affects a prompt still waiting in the queue; a running one has to be
+            // interrupted. Servers that ignore `prompt_id` interrupt whatever is running.
+            let delete = serde_json::json!({ "delete": [prompt_id] });
+            if let Err(e) = client.post(format!("{}/queue", base_url)).json(&delete).send().await {
+                log::warn!("Failed to remove ComfyUI prompt {} from the queue: {}", prompt_id, e);
+            }
+            let interrupt = serde_json::json!({ "prompt_id": prompt_id });
+            if let Err(e) = client.post(format!("{}/interrupt", base_url)).json(&interrupt).send().await {
+                log::warn!("Failed to interrupt ComfyUI prompt {}: {}", prompt_id, e);
+            }
+        });
+    }
+}
+
+pub struct ComfyUiClient {
+    client: Client,
+    retry: RetryPolicy,
+    base_url: String,
+    options: ComfyUiOptions,
+}
+
+impl ComfyUiClient {
+    pub fn new(base_url: Option<String>, options: ComfyUiOptions) -> Self {
+        Self {
+            client: Client::new(),
+            retry: RetryPolicy::default(),
+            base_url: base_url
+                .unwrap_or_else(|| "http://127.0.0.1:8188".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            options,
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Use a client built from the network settings (timeouts, proxy, root certificates)
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    async fn upload_image(
+        &self,
+        base64_data: &str,
+        file_name: String,
+        progress: &dyn ProgressSink,
+    ) -> Result<UploadResponse, ApiError> {
+        let bytes = STANDARD
+            .decode(base64_data)
+            .map_err(|e| ApiError::ParseError(format!("Invalid image base64: {}", e)))?;
+        let url = format!("{}/upload/image", self.base_url);
+
+        // Uploads overwrite by name, so they are safe to retry
+        let response = self
+            .retry
+            .send(progress, || {
+                let part = Part::bytes(bytes.clone()).file_name(file_name.clone()).mime_str("image/png")?;
+                let form = Form::new()
+                    .part("image", part)
+                    .text("type", "input")
+                    .text("overwrite", "true");
+                Ok(self.client.post(&url).multipart(form))
+            })
+            .await?;
+
+        let status = response.status();
+        let response_text = response.text().await?;
+        if !status.is_success() {
+            return Err(ApiError::from_status(status, truncate(&response_text, 200).to_string(), None, None));
+        }
+
+        serde_json::from_str(&response_text)
+            .map_err(|e| ApiError::ParseError(format!("{}: {}", e, truncate(&response_text, 200))))
+    }
+
+    /// Queue the workflow. Not retried: a failed response may still have queued it.
+    async fn queue_prompt(&self, workflow: Value) -> Result<String, ApiError> {
+        let body = serde_json::json!({ "prompt": workflow, "client_id": "bananaslice" });
+        let response = self
+            .client
+            .post(format!("{}/prompt", self.base_url))
+            .json(&body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let response_text = response.text().await?;
+        let queued = match serde_json::from_str::<QueueResponse>(&response_text) {
+            Ok(queued) => queued,
+            Err(_) if !status.is_success() => {
+                return Err(ApiError::from_status(status, truncate(&response_text, 200).to_string(), None, None));
+            }
+            Err(e) => return Err(ApiError::ParseError(format!("{}: {}", e, truncate(&response_text, 200)))),
+        };
+
+        // Validation failures come back as 400 with the offending nodes listed
+        if let Some(error) = queued.error {
+            let message = error.get("message").and_then(Value::as_str).unwrap_or("Workflow rejected");
+            let details = queued.node_errors.map(|e| e.to_string()).unwrap_or_default();
+            log::error!("ComfyUI rejected workflow: {} {}", message, details);
+            return Err(ApiError::BadRequest {
+                message: format!("{} {}", message, details).trim().to_string(),
+                field: Some("workflow".to_string()),
+            });
+        }
+        if !status.is_success() {
+            return Err(ApiError::from_status(status, truncate(&response_text, 200).to_string(), None, None));
+        }
+
+        queued
+            .prompt_id
+            .ok_or_else(|| ApiError::ParseError("Missing prompt_id in /prompt response".to_string()))
+    }
+
+    /// Poll `/history/{id}` until the prompt has finished executing
+    async fn wait_for_history(&self, prompt_id: &str) -> Result<HistoryEntry, ApiError> {
+        let deadline = Instant::now() + Duration::from_secs(self.options.timeout_secs);
+        let url = format!("{}/history/{}", self.base_url, prompt_id);
+
+        loop {
+            let mut history: serde_json::Map<String, Value> = self.client.get(&url).send().await?.json().await?;
+
+            if let Some(entry) = history.remove(prompt_id) {
+                let entry: HistoryEntry = serde_json::from_value(entry)
+                    .map_err(|e| ApiError::ParseError(format!("Invalid history entry: {}", e)))?;
+
+                match entry.status.as_ref() {
+                    Some(status) if status.status_str.as_deref() == Some("error") => {
+                        return Err(ApiError::Service("ComfyUI workflow execution failed".to_string()));
+                    }
+                    Some(status) if !status.completed => {}
+                    _ => return Ok(entry),
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(ApiError::Timeout(format!(
+                    "No result from ComfyUI after {}s",
+                    self.options.timeout_secs
+                )));
+            }
+            tokio::time::sleep(Duration::from_millis(self.options.poll_interval_ms)).await;
+        }
+    }
+
+    fn find_output_images(&self, entry: HistoryEntry) -> Vec<OutputImage> {
+        entry
+            .outputs
+            .into_iter()
+            .filter(|(node_id, _)| self.options.output_node.as_ref().map_or(true, |wanted| wanted == node_id))
+            .filter_map(|(_, output)| output.get("images").cloned())
+            .filter_map(|images| serde_json::from_value::<Vec<OutputImage>>(images).ok())
+            .flatten()
+            .filter(|image| image.folder_type == "output")
+            .collect()
+    }
+
+    async fn download_image(&self, image: &OutputImage, progress: &dyn ProgressSink) -> Result<String, ApiError> {
+        let url = format!("{}/view", self.base_url);
+        let response = self
+            .retry
+            .send(progress, || {
+                Ok(self.client.get(&url).query(&[
+                    ("filename", image.filename.as_str()),
+                    ("subfolder", image.subfolder.as_str()),
+                    ("type", image.folder_type.as_str()),
+                ]))
+            })
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ApiError::Service(format!("Failed to download {} ({})", image.filename, status)));
+        }
+
+        Ok(STANDARD.encode(response.bytes().await?))
+    }
+
+    /// Check that the ComfyUI server is reachable
+    pub async fn probe(&self) -> Result<(), ApiError> {
+        let response = self.client.get(format!("{}/system_stats", self.base_url)).send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let response_text = response.text().await?;
+        Err(ApiError::from_status(status, truncate(&response_text, 200).to_string(), None, None))
+    }
+
+    /// Upload the crop and mask, run the workflow and return its output images
+    ///
+    /// The number of variants is controlled by the workflow itself (for
+    /// example the batch size of its latent node); every saved output is returned.
+    pub async fn generate_fill(&self, request: &FillRequest<'_>) -> Result<Vec<GeneratedImage>, ApiError> {
+        if !self.options.workflow.is_object() {
+            return Err(ApiError::ParseError("ComfyUI provider requires a workflow object".to_string()));
+        }
+        if !request.reference_images.is_empty() {
+            log::warn!("ComfyUI workflow does not take reference images; ignoring {}", request.reference_images.len());
+        }
+
+        request.progress.stage(GenerationStage::Uploading);
+        let suffix = unique_suffix();
+        let image = self
+            .upload_image(request.image_base64, format!("bananaslice_{}_image.png", suffix), request.progress)
+            .await?;
+        let mask = self
+            .upload_image(request.mask_base64, format!("bananaslice_{}_mask.png", suffix), request.progress)
+            .await?;
+
+        let values = TemplateValues {
+            image: &uploaded_path(&image),
+            mask: &uploaded_path(&mask),
+            prompt: request.prompt,
+            seed: self.options.seed.unwrap_or((suffix % u32::MAX as u128) as u64),
+        };
+        let workflow = apply_template(&self.options.workflow, &values);
+
+        let prompt_id = self.queue_prompt(workflow).await?;
+        log::info!("Queued ComfyUI prompt: {}", prompt_id);
+        request.progress.stage(GenerationStage::Waiting);
+
+        // Cancelling the job drops this future; the guard then stops the prompt on the server
+        let queued = QueuedPrompt { client: self.client.clone(), base_url: self.base_url.clone(), prompt_id, done: false };
+        let entry = self.wait_for_history(&queued.prompt_id).await?;
+        queued.finish();
+        let outputs = self.find_output_images(entry);
+        if outputs.is_empty() {
+            return Err(ApiError::NoImageGenerated(ModelFeedback::default()));
+        }
+
+        request.progress.stage(GenerationStage::Decoding);
+        let mut images = Vec::with_capacity(outputs.len());
+        for (index, output) in outputs.iter().enumerate() {
+            log::info!("Downloading ComfyUI output: {}", output.filename);
+            let data = self.download_image(output, request.progress).await?;
+            images.push(GeneratedImage::new(index as u32, data));
+        }
+        Ok(images)
+    }
+}
+
+#[async_trait]
+impl ImageEditProvider for ComfyUiClient {
+    fn id(&self) -> &'static str {
+        "comfyui"
+    }
+
+    async fn inpaint(&self, request: &FillRequest<'_>) -> Result<Vec<GeneratedImage>, ApiError> {
+        self.generate_fill(request).await
+    }
+
+    /// Models live in the workflow, so `model` is not checked
+    async fn probe(&self, _model: Option<&str>) -> Result<(), ApiError> {
+        ComfyUiClient::probe(self).await
+    }
+
+    /// Nothing to pick: the workflow chooses its own models
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ApiError> {
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_server::{NoProgress, StubResponse, StubServer};
+    use serde_json::json;
+
+    #[test]
+    fn template_replaces_placeholders_and_types_seed() {
+        let workflow = json!({
+            "3": { "inputs": { "seed": "{{seed}}", "text": "photo of {{prompt}}" } },
+            "10": { "inputs": { "image": "{{image}}", "mask": ["{{mask}}"] } },
+        });
+        let values = TemplateValues { image: "in/a.png", mask: "in/b.png", prompt: "a cat", seed: 7 };
+
+        let result = apply_template(&workflow, &values);
+        assert_eq!(result["3"]["inputs"]["seed"], json!(7));
+        assert_eq!(result["3"]["inputs"]["text"], json!("photo of a cat"));
+        assert_eq!(result["10"]["inputs"]["image"], json!("in/a.png"));
+        assert_eq!(result["10"]["inputs"]["mask"][0], json!("in/b.png"));
+    }
+
+    fn fill_request() -> FillRequest<'static> {
+        FillRequest {
+            model: "",
+            prompt: "a cat",
+            image_base64: "aW1hZ2U=",
+            mask_base64: "bWFzaw==",
+            reference_images: &[],
+            candidate_count: 1,
+            progress: &NoProgress,
+        }
+    }
+
+    fn workflow_options() -> ComfyUiOptions {
+        ComfyUiOptions {
+            workflow: json!({ "1": { "inputs": { "image": "{{image}}", "seed": "{{seed}}" } } }),
+            seed: Some(5),
+            poll_interval_ms: 10,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_workflow_end_to_end() {
+        let server = StubServer::start(vec![
+            StubResponse::json(200, r#"{"name":"img.png","subfolder":"","type":"input"}"#),
+            StubResponse::json(200, r#"{"name":"mask.png","subfolder":"","type":"input"}"#),
+            StubResponse::json(200, r#"{"prompt_id":"abc","number":1,"node_errors":{}}"#),
+            StubResponse::json(200, r#"{}"#),
+            StubResponse::json(
+                200,
+                r#"{"abc":{"outputs":{"9":{"images":[{"filename":"out.png","subfolder":"","type":"output"}]}},"status":{"status_str":"success","completed":true}}}"#,
+            ),
+            StubResponse::bytes(200, "image/png", b"patch".to_vec()),
+        ])
+        .await;
+
+        let client = ComfyUiClient::new(Some(server.base_url.clone()), workflow_options());
+        let images = client.generate_fill(&fill_request()).await.unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].image_base64, STANDARD.encode(b"patch"));
+
+        let requests = server.requests();
+        let paths: Vec<&str> = requests.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["/upload/image", "/upload/image", "/prompt", "/history/abc", "/history/abc", "/view?filename=out.png&subfolder=&type=output"]
+        );
+
+        let queued = requests[2].json();
+        assert_eq!(queued["prompt"]["1"]["inputs"]["image"], json!("img.png"));
+        assert_eq!(queued["prompt"]["1"]["inputs"]["seed"], json!(5));
+    }
+
+    #[tokio::test]
+    async fn failures_are_classified_by_kind() {
+        // A rejected upload is classified by its status code
+        let server = StubServer::start(vec![StubResponse::json(413, r#"{"error":"too large"}"#)]).await;
+        let client = ComfyUiClient::new(Some(server.base_url.clone()), workflow_options());
+        let result = client.generate_fill(&fill_request()).await;
+        assert!(matches!(result, Err(ApiError::BadRequest { .. })), "{:?}", result);
+
+        // Running out of time while polling is a timeout
+        let server = StubServer::start(vec![
+            StubResponse::json(200, r#"{"name":"img.png","subfolder":"","type":"input"}"#),
+            StubResponse::json(200, r#"{"name":"mask.png","subfolder":"","type":"input"}"#),
+            StubResponse::json(200, r#"{"prompt_id":"abc","number":1,"node_errors":{}}"#),
+            StubResponse::json(200, r#"{}"#),
+        ])
+        .await;
+        let options = ComfyUiOptions { timeout_secs: 0, ..workflow_options() };
+        let client = ComfyUiClient::new(Some(server.base_url.clone()), options);
+        let result = client.generate_fill(&fill_request()).await;
+        assert!(matches!(result, Err(ApiError::Timeout(_))), "{:?}", result);
+
+        // A workflow that fails validation is the user's to fix, not a server fault
+        let server = StubServer::start(vec![
+            StubResponse::json(200, r#"{"name":"img.png","subfolder":"","type":"input"}"#),
+            StubResponse::json(200, r#"{"name":"mask.png","subfolder":"","type":"input"}"#),
+            StubResponse::json(
+                400,
+                r#"{"error":{"type":"prompt_outputs_failed_validation","message":"Prompt outputs failed validation"},"node_errors":{"1":{}}}"#,
+            ),
+        ])
+        .await;
+        let client = ComfyUiClient::new(Some(server.base_url.clone()), workflow_options());
+        let result = client.generate_fill(&fill_request()).await;
+        assert!(
+            matches!(result, Err(ApiError::BadRequest { ref message, .. }) if message.contains("failed validation")),
+            "{:?}",
+            result
+        );
+
+        // Anything else that is not JSON is classified by its status
+        let server = StubServer::start(vec![
+            StubResponse::json(200, r#"{"name":"img.png","subfolder":"","type":"input"}"#),
+            StubResponse::json(200, r#"{"name":"mask.png","subfolder":"","type":"input"}"#),
+            StubResponse::bytes(502, "text/html", b"<h1>Bad Gateway</h1>".to_vec()),
+        ])
+        .await;
+        let client = ComfyUiClient::new(Some(server.base_url.clone()), workflow_options());
+        let result = client.generate_fill(&fill_request()).await;
+        assert!(matches!(result, Err(ApiError::Service(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn cancelling_stops_the_queued_prompt() {
+        let mut responses = vec![
+            StubResponse::json(200, r#"{"name":"img.png","subfolder":"","type":"input"}"#),
+            StubResponse::json(200, r#"{"name":"mask.png","subfolder":"","type":"input"}"#),
+            StubResponse::json(200, r#"{"prompt_id":"abc","number":1,"node_errors":{}}"#),
+        ];
+        responses.extend(std::iter::repeat(StubResponse::json(200, "{}")).take(100));
+        let server = StubServer::start(responses).await;
+
+        let client = ComfyUiClient::new(Some(server.base_url.clone()), workflow_options());
+        let job = tokio::spawn(async move { client.generate_fill(&fill_request()).await.map(|_| ()) });
+        for _ in 0..200 {
+            if server.requests().iter().any(|r| r.path == "/history/abc") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        job.abort();
+        let _ = job.await;
+
+        for _ in 0..200 {
+            if server.requests().iter().any(|r| r.path == "/interrupt") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let requests = server.requests();
+        let delete = requests.iter().find(|r| r.path == "/queue").expect("prompt was not removed from the queue");
+        assert_eq!(delete.json(), json!({ "delete": ["abc"] }));
+        let interrupt = requests.iter().find(|r| r.path == "/interrupt").expect("prompt was not interrupted");
+        assert_eq!(interrupt.json(), json!({ "prompt_id": "abc" }));
+    }
+}
@@ -0,0 +1,146 @@
+// BananaSlice - API Errors
+// Internal provider errors and the typed error payload sent to the frontend
+
+use super::ModelFeedback;
+use reqwest::StatusCode;
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("HTTP request failed: {0}")]
+    RequestFailed(#[from] reqwest::Error),
+
+    #[error("API key not configured")]
+    ApiKeyMissing,
+
+    #[error("Invalid API key: {0}")]
+    InvalidApiKey(String),
+
+    #[error("Quota exceeded: {message}")]
+    QuotaExceeded { message: String, retry_after: Option<u64> },
+
+    #[error("Blocked by safety filters{}", .category.as_ref().map(|c| format!(" ({})", c)).unwrap_or_default())]
+    SafetyBlocked { category: Option<String>, feedback: ModelFeedback },
+
+    #[error("Invalid request: {message}")]
+    BadRequest { message: String, field: Option<String> },
+
+    #[error("Request timed out: {0}")]
+    Timeout(String),
+
+    #[error("API returned error: {0}")]
+    Service(String),
+
+    #[error("Failed to parse response: {0}")]
+    ParseError(String),
+
+    #[error("{}", no_image_message(.0))]
+    NoImageGenerated(ModelFeedback),
+
+    #[error("Unknown provider: {0}")]
+    UnknownProvider(String),
+}
+
+impl ApiError {
+    /// Classify a failed HTTP response by status code
+    pub fn from_status(status: StatusCode, message: String, retry_after: Option<u64>, field: Option<String>) -> Self {
+        match status.as_u16() {
+            401 | 403 => ApiError::InvalidApiKey(message),
+            429 => ApiError::QuotaExceeded { message, retry_after },
+            400 | 404 | 413 | 422 => ApiError::BadRequest { message, field },
+            408 | 504 => ApiError::Timeout(message),
+            _ => ApiError::Service(message),
+        }
+    }
+
+    /// Model feedback explaining why no image came back, when the provider sent any
+    pub fn feedback(&self) -> Option<&ModelFeedback> {
+        match self {
+            ApiError::SafetyBlocked { feedback, .. } | ApiError::NoImageGenerated(feedback) => Some(feedback),
+            _ => None,
+        }
+    }
+}
+
+/// Finish reasons for output withheld because it repeats existing material too closely
+const RECITATION_FINISH_REASONS: &[&str] = &["RECITATION", "IMAGE_RECITATION"];
+
+fn no_image_message(feedback: &ModelFeedback) -> String {
+    let reason = match feedback.finish_reason.as_deref() {
+        Some(reason) if RECITATION_FINISH_REASONS.contains(&reason) => {
+            " (the result matched existing content too closely; try rewording the prompt)"
+        }
+        _ => "",
+    };
+    let text = feedback.model_text.as_ref().map(|t| format!(": {}", t)).unwrap_or_default();
+    format!("No image generated{}{}", reason, text)
+}
+
+/// The first `max_chars` characters of a response body, for error messages and logs
+pub(crate) fn truncate(text: &str, max_chars: usize) -> &str {
+    text.char_indices().nth(max_chars).map_or(text, |(i, _)| &text[..i])
+}
+
+/// Machine-readable error category for the frontend
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GenerationError {
+    InvalidApiKey,
+    /// `retry_after` is in seconds, when the provider reported it
+    QuotaExceeded { retry_after: Option<u64> },
+    SafetyBlocked { category: Option<String> },
+    /// `finish_reason` tells why the model stopped, e.g. "RECITATION"
+    NoImageReturned { model_text: Option<String>, finish_reason: Option<String> },
+    Network,
+    Timeout,
+    BadRequest { field: Option<String> },
+    /// The encrypted credentials file holds the key but has not been unlocked
+    CredentialsLocked,
+    Cancelled,
+    Unknown,
+}
+
+/// Error payload returned in `GenerateResponse.error`
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerateError {
+    #[serde(flatten)]
+    pub kind: GenerationError,
+    /// Human-readable description for display
+    pub message: String,
+}
+
+impl GenerateError {
+    pub fn new(kind: GenerationError, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into() }
+    }
+}
+
+impl From<&ApiError> for GenerationError {
+    fn from(error: &ApiError) -> Self {
+        match error {
+            ApiError::RequestFailed(e) if e.is_timeout() => GenerationError::Timeout,
+            ApiError::RequestFailed(e) if e.is_decode() => GenerationError::Unknown,
+            ApiError::RequestFailed(_) => GenerationError::Network,
+            ApiError::ApiKeyMissing | ApiError::InvalidApiKey(_) => GenerationError::InvalidApiKey,
+            ApiError::QuotaExceeded { retry_after, .. } => GenerationError::QuotaExceeded { retry_after: *retry_after },
+            ApiError::SafetyBlocked { category, .. } => GenerationError::SafetyBlocked { category: category.clone() },
+            ApiError::BadRequest { field, .. } => GenerationError::BadRequest { field: field.clone() },
+            ApiError::Timeout(_) => GenerationError::Timeout,
+            ApiError::NoImageGenerated(feedback) => {
+                GenerationError::NoImageReturned {
+                    model_text: feedback.model_text.clone(),
+                    finish_reason: feedback.finish_reason.clone(),
+                }
+            }
+            ApiError::UnknownProvider(_) => GenerationError::BadRequest { field: Some("provider".to_string()) },
+            ApiError::Service(_) | ApiError::ParseError(_) => GenerationError::Unknown,
+        }
+    }
+}
+
+impl From<&ApiError> for GenerateError {
+    fn from(error: &ApiError) -> Self {
+        Self::new(error.into(), error.to_string())
+    }
+}
@@ -0,0 +1,832 @@
+// Nano Banana API Module
+// Handles communication with Google's Gemini Image API
+
+use super::aspect_ratio::{closest_ratio, SUPPORTED_ASPECT_RATIOS};
+use super::error::truncate;
+use super::preprocess::InputLimits;
+use super::retry::retry_after;
+use super::vertex::TokenSource;
+use super::{
+    resolve_model, ApiError, AuthStyle, FillRequest, GeneratedImage, GenerationStage, ImageEditProvider, ModelFeedback, ModelInfo,
+    RetryPolicy, SafetyRating, GEMINI_MODEL_ALIASES,
+};
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize)]
+struct GeminiRequest {
+    contents: Vec<Content>,
+    #[serde(rename = "generationConfig")]
+    generation_config: GenerationConfig,
+}
+
+#[derive(Debug, Serialize)]
+struct Content {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum Part {
+    Text { text: String },
+    InlineData { inline_data: InlineData },
+}
+
+#[derive(Debug, Serialize)]
+struct InlineData {
+    mime_type: String,
+    data: String,
+}
+
+#[derive(Debug, Serialize)]
+struct GenerationConfig {
+    #[serde(rename = "responseModalities")]
+    response_modalities: Vec<String>,
+    #[serde(rename = "imageConfig", skip_serializing_if = "Option::is_none")]
+    image_config: Option<ImageConfig>,
+    #[serde(rename = "candidateCount", skip_serializing_if = "Option::is_none")]
+    candidate_count: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+struct ImageConfig {
+    #[serde(rename = "aspectRatio")]
+    aspect_ratio: String,
+}
+
+/// Get image dimensions from base64 PNG data
+fn get_image_dimensions(base64_data: &str) -> Option<(u32, u32)> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    
+    let bytes = STANDARD.decode(base64_data).ok()?;
+    
+    // PNG header check and dimension extraction
+    if bytes.len() < 24 {
+        return None;
+    }
+    
+    // Check PNG magic number
+    if bytes[0..8] != [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A] {
+        return None;
+    }
+    
+    // Width and height are at bytes 16-19 and 20-23 (big-endian)
+    let width = u32::from_be_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
+    let height = u32::from_be_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]);
+    
+    Some((width, height))
+}
+
+/// Inputs beyond this many pixels are downscaled; the model outputs at its own resolution anyway
+const MAX_INPUT_PIXELS: u64 = 2048 * 2048;
+
+/// Calculate the closest supported aspect ratio for Gemini API
+fn calculate_aspect_ratio(width: u32, height: u32) -> String {
+    let (w, h) = closest_ratio(SUPPORTED_ASPECT_RATIOS, width, height);
+    log::info!("Image {}x{} ratio={:.3}, closest supported: {}:{}", width, height, width as f64 / height as f64, w, h);
+    format!("{}:{}", w, h)
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiResponse {
+    candidates: Option<Vec<Candidate>>,
+    #[serde(rename = "promptFeedback")]
+    prompt_feedback: Option<PromptFeedback>,
+    error: Option<GeminiError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PromptFeedback {
+    #[serde(rename = "blockReason")]
+    block_reason: Option<String>,
+    #[serde(rename = "safetyRatings", default)]
+    safety_ratings: Vec<SafetyRating>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Candidate {
+    content: Option<CandidateContent>,
+    index: Option<u32>,
+    #[serde(rename = "finishReason")]
+    finish_reason: Option<String>,
+    #[serde(rename = "safetyRatings", default)]
+    safety_ratings: Vec<SafetyRating>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CandidateContent {
+    parts: Vec<ResponsePart>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponsePart {
+    #[serde(rename = "inlineData")]
+    inline_data: Option<ResponseInlineData>,
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseInlineData {
+    #[serde(rename = "mimeType")]
+    mime_type: String,
+    data: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiError {
+    code: Option<u16>,
+    message: String,
+    status: Option<String>,
+    #[serde(default)]
+    details: Vec<serde_json::Value>,
+}
+
+/// Body of a failed request
+#[derive(Debug, Deserialize)]
+struct ErrorEnvelope {
+    error: GeminiError,
+}
+
+/// One page of `GET /models`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModelList {
+    #[serde(default)]
+    models: Vec<GeminiModel>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiModel {
+    name: String, // "models/<id>"
+    display_name: Option<String>,
+    description: Option<String>,
+    input_token_limit: Option<u32>,
+    output_token_limit: Option<u32>,
+    #[serde(default)]
+    supported_generation_methods: Vec<String>,
+}
+
+impl GeminiModel {
+    /// The list does not report output modalities, so image models are
+    /// recognised by name among those that support generateContent
+    fn into_image_model(self) -> Option<ModelInfo> {
+        let id = self.name.strip_prefix("models/").unwrap_or(&self.name).to_string();
+        let generates = self.supported_generation_methods.iter().any(|m| m == "generateContent");
+        if !generates || !id.contains("image") {
+            return None;
+        }
+        Some(ModelInfo {
+            display_name: self.display_name.unwrap_or_else(|| id.clone()),
+            description: self.description,
+            input_token_limit: self.input_token_limit,
+            output_token_limit: self.output_token_limit,
+            ..ModelInfo::from_id(&id)
+        })
+    }
+}
+
+/// Upper bound on model list pages fetched, in case a gateway keeps returning tokens
+const MAX_MODEL_PAGES: usize = 10;
+
+/// Finish reasons that mean the output was withheld by a safety filter
+const SAFETY_FINISH_REASONS: &[&str] = &[
+    "SAFETY",
+    "IMAGE_SAFETY",
+    "PROHIBITED_CONTENT",
+    "IMAGE_PROHIBITED_CONTENT",
+    "BLOCKLIST",
+    "SPII",
+];
+
+/// Find a `google.rpc.*` detail entry by type suffix
+fn error_detail<'a>(error: &'a GeminiError, type_suffix: &str) -> Option<&'a serde_json::Value> {
+    error.details.iter().find(|detail| {
+        detail
+            .get("@type")
+            .and_then(|t| t.as_str())
+            .is_some_and(|t| t.ends_with(type_suffix))
+    })
+}
+
+/// Parse a protobuf duration such as "12s" or "1.5s" into whole seconds
+fn parse_retry_delay(delay: &str) -> Option<u64> {
+    delay.strip_suffix('s')?.parse::<f64>().ok().map(|secs| secs.ceil() as u64)
+}
+
+/// Map a Gemini error object to a typed error using its `status`, `code` and details
+fn classify_error(error: GeminiError, header_retry_after: Option<u64>) -> ApiError {
+    let reason = error_detail(&error, "ErrorInfo")
+        .and_then(|info| info.get("reason"))
+        .and_then(|r| r.as_str());
+    if reason == Some("API_KEY_INVALID") {
+        return ApiError::InvalidApiKey(error.message);
+    }
+
+    match error.status.as_deref() {
+        Some("UNAUTHENTICATED") | Some("PERMISSION_DENIED") => ApiError::InvalidApiKey(error.message),
+        Some("RESOURCE_EXHAUSTED") => {
+            let retry_after = error_detail(&error, "RetryInfo")
+                .and_then(|info| info.get("retryDelay"))
+                .and_then(|d| d.as_str())
+                .and_then(parse_retry_delay)
+                .or(header_retry_after);
+            ApiError::QuotaExceeded { message: error.message, retry_after }
+        }
+        Some("INVALID_ARGUMENT") | Some("FAILED_PRECONDITION") | Some("NOT_FOUND") => {
+            let field = error_detail(&error, "BadRequest")
+                .and_then(|info| info.pointer("/fieldViolations/0/field"))
+                .and_then(|f| f.as_str())
+                .map(str::to_string);
+            ApiError::BadRequest { message: error.message, field }
+        }
+        Some("DEADLINE_EXCEEDED") => ApiError::Timeout(error.message),
+        _ => match error.code.and_then(|code| StatusCode::from_u16(code).ok()) {
+            Some(code) => ApiError::from_status(code, error.message, header_retry_after, None),
+            None => ApiError::Service(error.message),
+        },
+    }
+}
+
+/// Typed error for a non-success response, falling back to its HTTP status
+fn error_from_body(status: StatusCode, header_retry_after: Option<u64>, body: &str) -> ApiError {
+    match serde_json::from_str::<ErrorEnvelope>(body) {
+        Ok(envelope) => classify_error(envelope.error, header_retry_after),
+        Err(_) => ApiError::from_status(status, truncate(body, 200).to_string(), header_retry_after, None),
+    }
+}
+
+/// How Gemini requests are authorized
+enum Credential {
+    /// Consumer API key, sent according to `auth`
+    ApiKey { key: String, auth: AuthStyle },
+    /// Vertex AI service account exchanging signed JWTs for access tokens
+    ServiceAccount(TokenSource),
+}
+
+pub struct NanoBananaClient {
+    client: Client,
+    retry: RetryPolicy,
+    credential: Credential,
+    base_url: String,
+}
+
+impl NanoBananaClient {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            client: Client::new(),
+            retry: RetryPolicy::default(),
+            credential: Credential::ApiKey { key: api_key, auth: AuthStyle::GoogApiKey },
+            base_url: "https://generativelanguage.googleapis.com/v1beta".to_string(),
+        }
+    }
+
+    pub fn with_base_url(api_key: String, base_url: String) -> Self {
+        Self {
+            client: Client::new(),
+            retry: RetryPolicy::default(),
+            credential: Credential::ApiKey { key: api_key, auth: AuthStyle::GoogApiKey },
+            base_url,
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Use a client built from the network settings (timeouts, proxy, root certificates)
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Send the key some other way than `x-goog-api-key`, e.g. for gateways expecting bearer tokens
+    pub fn with_auth(mut self, auth: AuthStyle) -> Self {
+        if let Credential::ApiKey { auth: current, .. } = &mut self.credential {
+            *current = auth;
+        }
+        self
+    }
+
+    /// Call Gemini models on Vertex AI (see `vertex_base_url`) with service-account tokens
+    pub fn vertex(tokens: TokenSource, base_url: String) -> Self {
+        Self {
+            client: Client::new(),
+            retry: RetryPolicy::default(),
+            credential: Credential::ServiceAccount(tokens),
+            base_url,
+        }
+    }
+
+    /// Header style and secret for a request; service accounts trade a signed JWT for a (cached) access token
+    async fn authorization(&self) -> Result<(AuthStyle, String), ApiError> {
+        Ok(match &self.credential {
+            Credential::ApiKey { key, auth } => (auth.clone(), key.clone()),
+            Credential::ServiceAccount(tokens) => (AuthStyle::Bearer, tokens.access_token().await?),
+        })
+    }
+
+    /// Check the credential with a metadata request, without generating anything
+    ///
+    /// Fetches `model` when given, otherwise lists a single model. Vertex AI
+    /// has no model list, so it always fetches a model (the default alias if none is given).
+    pub async fn probe(&self, model: Option<&str>) -> Result<(), ApiError> {
+        let model = match (&self.credential, model) {
+            (Credential::ServiceAccount(_), None) => Some("nano-banana"),
+            (_, model) => model,
+        };
+        let url = match model {
+            Some(model) => format!("{}/models/{}", self.base_url, resolve_model(model)),
+            None => format!("{}/models?pageSize=1", self.base_url),
+        };
+
+        let (auth, secret) = self.authorization().await?;
+        let response = auth.apply(self.client.get(&url), &secret).send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let header_retry_after = retry_after(&response).map(|d| d.as_secs());
+        let body = response.text().await?;
+        log::warn!("Gemini probe failed with status {}", status);
+        Err(error_from_body(status, header_retry_after, &body))
+    }
+
+    /// List the image models this key can use
+    ///
+    /// Vertex AI has no list endpoint for publisher models, so it reports the alias table.
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, ApiError> {
+        if let Credential::ServiceAccount(_) = self.credential {
+            return Ok(GEMINI_MODEL_ALIASES
+                .iter()
+                .map(|entry| ModelInfo { display_name: entry.display_name.to_string(), ..ModelInfo::from_id(entry.model_id) })
+                .collect());
+        }
+
+        let (auth, secret) = self.authorization().await?;
+        let url = format!("{}/models", self.base_url);
+        let mut models = Vec::new();
+        let mut page_token: Option<String> = None;
+        for _ in 0..MAX_MODEL_PAGES {
+            let mut builder = self.client.get(&url).query(&[("pageSize", "1000")]);
+            if let Some(token) = &page_token {
+                builder = builder.query(&[("pageToken", token)]);
+            }
+            let response = auth.apply(builder, &secret).send().await?;
+            let status = response.status();
+            let header_retry_after = retry_after(&response).map(|d| d.as_secs());
+            let body = response.text().await?;
+            if !status.is_success() {
+                return Err(error_from_body(status, header_retry_after, &body));
+            }
+
+            let page: ModelList = serde_json::from_str(&body)
+                .map_err(|e| ApiError::ParseError(format!("{}: {}", e, truncate(&body, 200))))?;
+            models.extend(page.models.into_iter().filter_map(GeminiModel::into_image_model));
+            page_token = page.next_page_token.filter(|token| !token.is_empty());
+            if page_token.is_none() {
+                break;
+            }
+        }
+        log::info!("Found {} image models", models.len());
+        Ok(models)
+    }
+
+    /// Generate fill for a masked region
+    /// 
+    /// # Arguments
+    /// * `model` - Resolved Gemini model ID
+    /// * `request` - Prompt, source image, mask (white = generate, black = keep),
+    ///   optional reference images and the number of candidates to request
+    pub async fn generate_fill(&self, model: &str, request: &FillRequest<'_>) -> Result<Vec<GeneratedImage>, ApiError> {
+        let FillRequest { prompt, image_base64, mask_base64, reference_images, candidate_count, progress, .. } = *request;
+        progress.stage(GenerationStage::Uploading);
+
+        let url = format!("{}/models/{}:generateContent", self.base_url, model);
+
+        // Build parts array starting with source image and mask
+        let mut parts = vec![
+            // Source image (primary - this is what we're editing)
+            Part::InlineData {
+                inline_data: InlineData {
+                    mime_type: "image/png".to_string(),
+                    data: image_base64.to_string(),
+                },
+            },
+            // Mask image
+            Part::InlineData {
+                inline_data: InlineData {
+                    mime_type: "image/png".to_string(),
+                    data: mask_base64.to_string(),
+                },
+            },
+        ];
+
+        // Add reference images if provided
+        for (i, ref_image) in reference_images.iter().enumerate() {
+            log::info!("Adding reference image {} ({} bytes)", i + 1, ref_image.len());
+            parts.push(Part::InlineData {
+                inline_data: InlineData {
+                    mime_type: "image/png".to_string(),
+                    data: ref_image.to_string(),
+                },
+            });
+        }
+
+        // Build prompt text based on whether we have reference images
+        let prompt_text = if reference_images.is_empty() {
+            format!(
+                "Edit this image. The second image is a mask where white areas should be replaced. \
+                In the white masked areas, generate: {}. \
+                Keep the black areas unchanged. Match the style and lighting of the original image.",
+                prompt
+            )
+        } else {
+            format!(
+                "Edit the first image. The second image is a mask where white areas should be replaced. \
+                The additional images are references to help guide the generation. \
+                In the white masked areas, generate: {}. \
+                Use the reference images as context for the generation. \
+                Keep the black areas unchanged.",
+                prompt
+            )
+        };
+
+        // Add prompt text
+        parts.push(Part::Text { text: prompt_text });
+
+        // Only set explicit aspect ratio when there are reference images
+        // (without refs, the API correctly infers from the single input image)
+        let image_config = if !reference_images.is_empty() {
+            get_image_dimensions(image_base64)
+                .map(|(w, h)| {
+                    let ratio = calculate_aspect_ratio(w, h);
+                    log::info!("Reference images present - setting aspectRatio to: {}", ratio);
+                    ImageConfig { aspect_ratio: ratio }
+                })
+        } else {
+            None
+        };
+
+        let request = GeminiRequest {
+            contents: vec![Content { parts }],
+            generation_config: GenerationConfig {
+                // Image-only output makes some models refuse outright; text lets them explain a refusal
+                response_modalities: vec!["TEXT".to_string(), "IMAGE".to_string()],
+                image_config,
+                candidate_count: (candidate_count > 1).then_some(candidate_count),
+            },
+        };
+
+        let (auth, secret) = self.authorization().await?;
+
+        // Send request
+        log::info!("Sending request to Gemini API: {}", model);
+        progress.stage(GenerationStage::Waiting);
+        
+        let response = self
+            .retry
+            .send(progress, || Ok(auth.apply(self.client.post(&url), &secret).json(&request)))
+            .await?;
+
+        let status = response.status();
+        let header_retry_after = retry_after(&response).map(|d| d.as_secs());
+        let response_text = response.text().await?;
+        
+        log::info!("API response status: {}", status);
+        progress.stage(GenerationStage::Decoding);
+        
+        // Parse response
+        let gemini_response: GeminiResponse = match serde_json::from_str(&response_text) {
+            Ok(parsed) => parsed,
+            Err(_) if !status.is_success() => {
+                return Err(ApiError::from_status(status, truncate(&response_text, 200).to_string(), header_retry_after, None));
+            }
+            Err(e) => return Err(ApiError::ParseError(format!("{}: {}", e, truncate(&response_text, 200)))),
+        };
+
+        // Check for API error
+        if let Some(error) = gemini_response.error {
+            log::error!("Gemini API error ({:?}): {}", error.status, error.message);
+            return Err(classify_error(error, header_retry_after));
+        }
+
+        // A blocked prompt comes back without candidates
+        let prompt_feedback = gemini_response.prompt_feedback;
+        let block_reason = prompt_feedback.as_ref().and_then(|f| f.block_reason.clone());
+        let Some(candidates) = gemini_response.candidates else {
+            log::error!("No candidates in response. Full response: {}", truncate(&response_text, 500));
+            let feedback = ModelFeedback {
+                block_reason: block_reason.clone(),
+                safety_ratings: prompt_feedback.map(|f| f.safety_ratings).unwrap_or_default(),
+                ..ModelFeedback::default()
+            };
+            return Err(match block_reason {
+                Some(category) => ApiError::SafetyBlocked { category: Some(category), feedback },
+                None => ApiError::NoImageGenerated(feedback),
+            });
+        };
+        
+        log::info!("Got {} candidates", candidates.len());
+        
+        // Feedback from the first candidate that produced no image, reported if none did
+        let mut images = Vec::new();
+        let mut failed: Option<ModelFeedback> = None;
+        for (position, candidate) in candidates.into_iter().enumerate() {
+            let index = candidate.index.unwrap_or(position as u32);
+            let parts = candidate.content.map(|content| content.parts).unwrap_or_default();
+            log::info!("Candidate {} has {} parts (finish reason: {:?})", index, parts.len(), candidate.finish_reason);
+
+            let mut texts = Vec::new();
+            let mut image_data = None;
+            for (i, part) in parts.into_iter().enumerate() {
+                log::info!("Part {}: text={}, inline_data={}", i, part.text.is_some(), part.inline_data.is_some());
+                if let Some(text) = part.text {
+                    log::info!("Found text part: {}", truncate(&text, 200));
+                    texts.push(text);
+                }
+                if let Some(inline_data) = part.inline_data {
+                    log::info!("Found inline_data with mime_type: {}", inline_data.mime_type);
+                    if inline_data.mime_type.starts_with("image/") && image_data.is_none() {
+                        log::info!("Found image data ({} bytes)", inline_data.data.len());
+                        image_data = Some(inline_data.data);
+                    }
+                }
+            }
+
+            let model_text = (!texts.is_empty()).then(|| texts.join("\n"));
+            match image_data {
+                Some(data) => images.push(GeneratedImage {
+                    index,
+                    image_base64: data,
+                    finish_reason: candidate.finish_reason,
+                    model_text,
+                    safety_ratings: candidate.safety_ratings,
+                }),
+                None if failed.is_none() => {
+                    failed = Some(ModelFeedback {
+                        model_text,
+                        finish_reason: candidate.finish_reason,
+                        block_reason: block_reason.clone(),
+                        safety_ratings: candidate.safety_ratings,
+                    })
+                }
+                None => {}
+            }
+        }
+
+        if images.is_empty() {
+            log::error!("No image found in response parts");
+            let feedback = failed.unwrap_or_default();
+            let safety_reason = feedback
+                .finish_reason
+                .clone()
+                .filter(|reason| SAFETY_FINISH_REASONS.contains(&reason.as_str()))
+                .or_else(|| block_reason.clone());
+            return Err(match safety_reason {
+                Some(category) => ApiError::SafetyBlocked { category: Some(category), feedback },
+                None => ApiError::NoImageGenerated(feedback),
+            });
+        }
+        Ok(images)
+    }
+}
+
+#[async_trait]
+impl ImageEditProvider for NanoBananaClient {
+    fn id(&self) -> &'static str {
+        match self.credential {
+            Credential::ApiKey { .. } => "gemini",
+            Credential::ServiceAccount(_) => "vertex",
+        }
+    }
+
+    async fn inpaint(&self, request: &FillRequest<'_>) -> Result<Vec<GeneratedImage>, ApiError> {
+        self.generate_fill(resolve_model(request.model), request).await
+    }
+
+    async fn probe(&self, model: Option<&str>) -> Result<(), ApiError> {
+        NanoBananaClient::probe(self, model).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ApiError> {
+        NanoBananaClient::list_models(self).await
+    }
+
+    fn input_limits(&self) -> InputLimits {
+        InputLimits { max_pixels: Some(MAX_INPUT_PIXELS), aspect_ratios: SUPPORTED_ASPECT_RATIOS }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_server::{NoProgress, StubResponse, StubServer};
+    use crate::api::{GenerateError, GenerationError};
+
+    fn fill_request() -> FillRequest<'static> {
+        FillRequest {
+            model: "gemini-2.5-flash-image",
+            prompt: "a red balloon",
+            image_base64: "aW1hZ2U=",
+            mask_base64: "bWFzaw==",
+            reference_images: &[],
+            candidate_count: 1,
+            progress: &NoProgress,
+        }
+    }
+
+    async fn fill_with_response(body: &str) -> Result<Vec<GeneratedImage>, ApiError> {
+        let server = StubServer::start(vec![StubResponse::json(200, body)]).await;
+        let client = NanoBananaClient::with_base_url("key".to_string(), server.base_url.clone());
+        client.generate_fill("gemini-2.5-flash-image", &fill_request()).await
+    }
+
+    #[tokio::test]
+    async fn sends_key_in_header_not_url() {
+        let server = StubServer::start(vec![StubResponse::json(
+            200,
+            r#"{"candidates":[{"content":{"parts":[{"inlineData":{"mimeType":"image/png","data":"cG5n"}}]}}]}"#,
+        )])
+        .await;
+        let client = NanoBananaClient::with_base_url("secret".to_string(), server.base_url.clone());
+        client.generate_fill("gemini-2.5-flash-image", &fill_request()).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/models/gemini-2.5-flash-image:generateContent");
+        assert_eq!(requests[0].headers["x-goog-api-key"], "secret");
+        assert_eq!(requests[0].json()["generationConfig"]["responseModalities"], serde_json::json!(["TEXT", "IMAGE"]));
+    }
+
+    #[tokio::test]
+    async fn text_only_reply_carries_model_feedback() {
+        let result = fill_with_response(
+            r#"{"candidates":[{"content":{"parts":[{"text":"I can't edit photos of real people."}]},
+                "finishReason":"IMAGE_SAFETY",
+                "safetyRatings":[{"category":"HARM_CATEGORY_HARASSMENT","probability":"MEDIUM","blocked":true}]}]}"#,
+        )
+        .await;
+
+        let Err(ApiError::SafetyBlocked { category, feedback }) = result else {
+            panic!("expected a safety block, got {:?}", result);
+        };
+        assert_eq!(category.as_deref(), Some("IMAGE_SAFETY"));
+        assert_eq!(feedback.model_text.as_deref(), Some("I can't edit photos of real people."));
+        assert_eq!(feedback.safety_ratings.len(), 1);
+        assert!(feedback.safety_ratings[0].blocked);
+    }
+
+    #[tokio::test]
+    async fn recitation_is_not_a_safety_block() {
+        let result = fill_with_response(r#"{"candidates":[{"content":{"parts":[]},"finishReason":"IMAGE_RECITATION"}]}"#).await;
+
+        let Err(ApiError::NoImageGenerated(feedback)) = &result else {
+            panic!("expected no image, got {:?}", result);
+        };
+        assert_eq!(feedback.finish_reason.as_deref(), Some("IMAGE_RECITATION"));
+        let error = GenerateError::from(&result.unwrap_err());
+        let GenerationError::NoImageReturned { finish_reason, .. } = &error.kind else {
+            panic!("expected no_image_returned, got {:?}", error.kind);
+        };
+        assert_eq!(finish_reason.as_deref(), Some("IMAGE_RECITATION"));
+        assert!(error.message.contains("existing content"), "{}", error.message);
+    }
+
+    #[tokio::test]
+    async fn blocked_prompt_reports_block_reason() {
+        let result = fill_with_response(r#"{"promptFeedback":{"blockReason":"PROHIBITED_CONTENT"}}"#).await;
+
+        let Err(ApiError::SafetyBlocked { category, feedback }) = result else {
+            panic!("expected a safety block, got {:?}", result);
+        };
+        assert_eq!(category.as_deref(), Some("PROHIBITED_CONTENT"));
+        assert_eq!(feedback.block_reason.as_deref(), Some("PROHIBITED_CONTENT"));
+    }
+
+    #[tokio::test]
+    async fn image_keeps_accompanying_text() {
+        let images = fill_with_response(
+            r#"{"candidates":[{"content":{"parts":[{"text":"Here you go"},
+                {"inlineData":{"mimeType":"image/png","data":"cG5n"}}]},"finishReason":"STOP"}]}"#,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(images[0].image_base64, "cG5n");
+        assert_eq!(images[0].model_text.as_deref(), Some("Here you go"));
+        assert_eq!(images[0].finish_reason.as_deref(), Some("STOP"));
+    }
+
+    #[tokio::test]
+    async fn unparseable_body_is_truncated_on_a_char_boundary() {
+        // The 200th byte falls inside a two-byte character
+        let body = format!("{}é and more", "a".repeat(199));
+        let result = fill_with_response(&body).await;
+
+        let Err(ApiError::ParseError(message)) = result else {
+            panic!("expected a parse error, got {:?}", result);
+        };
+        assert!(message.ends_with(&format!("{}é", "a".repeat(199))), "{}", message);
+    }
+
+    fn gemini_error(json: &str) -> GeminiError {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn classifies_invalid_key_from_error_info() {
+        let error = gemini_error(
+            r#"{"code":400,"message":"API key not valid","status":"INVALID_ARGUMENT",
+                "details":[{"@type":"type.googleapis.com/google.rpc.ErrorInfo","reason":"API_KEY_INVALID"}]}"#,
+        );
+        assert!(matches!(classify_error(error, None), ApiError::InvalidApiKey(_)));
+    }
+
+    #[test]
+    fn classifies_quota_with_retry_delay() {
+        let error = gemini_error(
+            r#"{"code":429,"message":"Quota exceeded","status":"RESOURCE_EXHAUSTED",
+                "details":[{"@type":"type.googleapis.com/google.rpc.RetryInfo","retryDelay":"12.5s"}]}"#,
+        );
+        assert!(matches!(
+            classify_error(error, Some(1)),
+            ApiError::QuotaExceeded { retry_after: Some(13), .. }
+        ));
+    }
+
+    #[test]
+    fn classifies_bad_request_field() {
+        let error = gemini_error(
+            r#"{"code":400,"message":"Bad image","status":"INVALID_ARGUMENT",
+                "details":[{"@type":"type.googleapis.com/google.rpc.BadRequest",
+                            "fieldViolations":[{"field":"contents[0].parts[0]","description":"bad"}]}]}"#,
+        );
+        assert!(matches!(
+            classify_error(error, None),
+            ApiError::BadRequest { field: Some(f), .. } if f == "contents[0].parts[0]"
+        ));
+    }
+
+    #[test]
+    fn falls_back_to_http_code() {
+        let error = gemini_error(r#"{"code":503,"message":"Overloaded"}"#);
+        assert!(matches!(classify_error(error, None), ApiError::Service(_)));
+    }
+
+    #[tokio::test]
+    async fn probe_fetches_model_metadata_with_key_header() {
+        let server = StubServer::start(vec![
+            StubResponse::json(200, r#"{"models":[]}"#),
+            StubResponse::json(404, r#"{"error":{"code":404,"message":"models/nope is not found","status":"NOT_FOUND"}}"#),
+        ])
+        .await;
+        let client = NanoBananaClient::with_base_url("secret".to_string(), server.base_url.clone());
+
+        client.probe(None).await.unwrap();
+        let result = client.probe(Some("nope")).await;
+        assert!(matches!(result, Err(ApiError::BadRequest { .. })), "got {:?}", result);
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/models?pageSize=1");
+        assert_eq!(requests[0].headers["x-goog-api-key"], "secret");
+        assert_eq!(requests[1].path, "/models/nope");
+    }
+
+    #[tokio::test]
+    async fn lists_image_models_across_pages() {
+        let server = StubServer::start(vec![
+            StubResponse::json(
+                200,
+                r#"{"models":[
+                    {"name":"models/gemini-2.5-flash-image","displayName":"Nano Banana","inputTokenLimit":32768,
+                     "outputTokenLimit":32768,"supportedGenerationMethods":["generateContent","countTokens"]},
+                    {"name":"models/gemini-2.5-flash","supportedGenerationMethods":["generateContent"]}
+                ],"nextPageToken":"page-2"}"#,
+            ),
+            StubResponse::json(
+                200,
+                r#"{"models":[
+                    {"name":"models/imagen-4.0-generate-001","supportedGenerationMethods":["predict"]},
+                    {"name":"models/my-image-tune","supportedGenerationMethods":["generateContent"]}
+                ]}"#,
+            ),
+        ])
+        .await;
+        let client = NanoBananaClient::with_base_url("secret".to_string(), server.base_url.clone());
+
+        let models = client.list_models().await.unwrap();
+        let ids: Vec<&str> = models.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["gemini-2.5-flash-image", "my-image-tune"]);
+        assert_eq!(models[0].alias.as_deref(), Some("nano-banana"));
+        assert_eq!(models[0].input_token_limit, Some(32768));
+        assert_eq!(models[1].display_name, "my-image-tune");
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/models?pageSize=1000");
+        assert_eq!(requests[1].path, "/models?pageSize=1000&pageToken=page-2");
+    }
+}
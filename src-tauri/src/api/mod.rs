@@ -0,0 +1,37 @@
+// BananaSlice - Image Generation API Module
+// Provider abstraction over the image editing backends
+
+mod aspect_ratio;
+mod auth;
+mod automatic1111;
+mod comfyui;
+mod error;
+mod gemini;
+mod models;
+mod network;
+mod openai;
+mod preprocess;
+mod provider;
+mod retry;
+mod validation;
+mod vertex;
+#[cfg(test)]
+mod test_server;
+
+pub use aspect_ratio::{calculate_adjustment, AspectRatioAdjustment, SelectionRect};
+pub use auth::AuthStyle;
+pub use automatic1111::Automatic1111Client;
+pub use comfyui::ComfyUiClient;
+pub use error::{ApiError, GenerateError, GenerationError};
+pub use gemini::NanoBananaClient;
+pub use models::{resolve_model, ModelInfo, GEMINI_MODEL_ALIASES};
+pub use network::NetworkSettings;
+pub use openai::OpenAiImagesClient;
+pub use preprocess::{prepare_inputs, restore_output, InputLimits, InputTransform};
+pub use provider::{
+    create_provider, FillRequest, GeneratedImage, GenerationStage, ImageEditProvider, ModelFeedback, ProgressSink,
+    ProviderConfig, SafetyRating, DEFAULT_PROVIDER,
+};
+pub use retry::RetryPolicy;
+pub use validation::KeyValidation;
+pub use vertex::{vertex_base_url, ServiceAccountKey, TokenSource, VertexOptions};
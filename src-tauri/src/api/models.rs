@@ -0,0 +1,74 @@
+// BananaSlice - Model Catalog
+// Friendly model aliases and the model metadata returned by `list_models`
+
+use serde::Serialize;
+
+/// A friendly name for a provider model ID
+pub struct ModelAlias {
+    pub alias: &'static str,
+    pub model_id: &'static str,
+    pub display_name: &'static str,
+}
+
+/// Gemini image models offered in the model selector, newest first
+pub const GEMINI_MODEL_ALIASES: &[ModelAlias] = &[
+    ModelAlias { alias: "nano-banana-pro", model_id: "gemini-3-pro-image-preview", display_name: "Nano Banana Pro" },
+    ModelAlias { alias: "nano-banana-2", model_id: "gemini-3.1-flash-image", display_name: "Nano Banana 2" },
+    ModelAlias { alias: "nano-banana", model_id: "gemini-2.5-flash-image", display_name: "Nano Banana (Fast)" },
+];
+
+/// Resolve model name: known aliases map to Gemini models, otherwise use as-is
+pub fn resolve_model(model: &str) -> &str {
+    GEMINI_MODEL_ALIASES
+        .iter()
+        .find(|entry| entry.alias == model)
+        .map_or(model, |entry| entry.model_id) // Allow custom model IDs
+}
+
+/// The alias for a model ID, if it has one
+pub fn alias_for(model_id: &str) -> Option<&'static str> {
+    GEMINI_MODEL_ALIASES
+        .iter()
+        .find(|entry| entry.model_id == model_id)
+        .map(|entry| entry.alias)
+}
+
+/// An image-capable model offered by a provider
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelInfo {
+    /// ID to send as `GenerateRequest.model`
+    pub id: String,
+    pub display_name: String,
+    /// Friendly alias from `GEMINI_MODEL_ALIASES`, when the model has one
+    pub alias: Option<String>,
+    pub description: Option<String>,
+    pub input_token_limit: Option<u32>,
+    pub output_token_limit: Option<u32>,
+}
+
+impl ModelInfo {
+    /// Entry for a model known only by its ID
+    pub fn from_id(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            display_name: id.to_string(),
+            alias: alias_for(id).map(str::to_string),
+            description: None,
+            input_token_limit: None,
+            output_token_limit: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_resolve_both_ways_and_custom_ids_pass_through() {
+        assert_eq!(resolve_model("nano-banana"), "gemini-2.5-flash-image");
+        assert_eq!(resolve_model("my-tuned-model"), "my-tuned-model");
+        assert_eq!(alias_for("gemini-3-pro-image-preview"), Some("nano-banana-pro"));
+        assert_eq!(ModelInfo::from_id("gemini-3.1-flash-image").alias.as_deref(), Some("nano-banana-2"));
+    }
+}
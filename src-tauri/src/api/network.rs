@@ -0,0 +1,163 @@
+// BananaSlice - Network Settings
+// Timeouts, proxy, extra root certificates and user agent shared by every provider's HTTP client
+
+use super::ApiError;
+use reqwest::{Certificate, Client, Proxy};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Persisted network settings; zero disables a timeout
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// Seconds to establish the TCP and TLS connection
+    pub connect_timeout_secs: u64,
+    /// Seconds to wait for each read from the server, so a stalled connection fails
+    pub read_timeout_secs: u64,
+    /// Seconds for a whole request, including the response body
+    pub total_timeout_secs: u64,
+    pub proxy: Option<ProxySettings>,
+    /// Extra trusted root certificates as a PEM bundle, e.g. a corporate CA
+    pub root_certificates_pem: Option<String>,
+    /// Appended to the "BananaSlice/<version>" user agent
+    pub user_agent_suffix: Option<String>,
+}
+
+impl Default for NetworkSettings {
+    /// Generous enough for slow image models, short enough that a dead connection is noticed
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 15,
+            read_timeout_secs: 180,
+            total_timeout_secs: 300,
+            proxy: None,
+            root_certificates_pem: None,
+            user_agent_suffix: None,
+        }
+    }
+}
+
+/// Proxy for HTTP and HTTPS traffic; the password is kept in the keystore, not here
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProxySettings {
+    /// e.g. "http://proxy.corp.example:3128"
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+}
+
+fn invalid(field: &str, message: String) -> ApiError {
+    ApiError::BadRequest { message, field: Some(field.to_string()) }
+}
+
+impl NetworkSettings {
+    pub fn user_agent(&self) -> String {
+        let base = format!("BananaSlice/{}", env!("CARGO_PKG_VERSION"));
+        match self.user_agent_suffix.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            Some(suffix) => format!("{} {}", base, suffix),
+            None => base,
+        }
+    }
+
+    /// Build the HTTP client every provider uses
+    pub fn build_client(&self, proxy_password: Option<&str>) -> Result<Client, ApiError> {
+        let mut builder = Client::builder().user_agent(self.user_agent());
+        if self.connect_timeout_secs > 0 {
+            builder = builder.connect_timeout(Duration::from_secs(self.connect_timeout_secs));
+        }
+        if self.read_timeout_secs > 0 {
+            builder = builder.read_timeout(Duration::from_secs(self.read_timeout_secs));
+        }
+        if self.total_timeout_secs > 0 {
+            builder = builder.timeout(Duration::from_secs(self.total_timeout_secs));
+        }
+
+        if let Some(settings) = self.proxy.as_ref().filter(|proxy| !proxy.url.trim().is_empty()) {
+            let mut proxy = Proxy::all(settings.url.trim())
+                .map_err(|e| invalid("proxy.url", format!("Invalid proxy URL: {}", e)))?;
+            if let Some(username) = settings.username.as_deref().filter(|name| !name.is_empty()) {
+                proxy = proxy.basic_auth(username, proxy_password.unwrap_or_default());
+            }
+            builder = builder.proxy(proxy);
+        }
+
+        if let Some(pem) = self.root_certificates_pem.as_deref().filter(|pem| !pem.trim().is_empty()) {
+            let certificates = Certificate::from_pem_bundle(pem.as_bytes())
+                .map_err(|e| invalid("root_certificates_pem", format!("Invalid PEM certificates: {}", e)))?;
+            if certificates.is_empty() {
+                return Err(invalid("root_certificates_pem", "No certificates found in PEM".to_string()));
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_server::{StubResponse, StubServer};
+
+    const TEST_CA_PEM: &str = "-----BEGIN CERTIFICATE-----\n\
+MIIBkzCCATmgAwIBAgIUTiHCNcOX6uCnVkQ9dpLyjIShjeQwCgYIKoZIzj0EAwIw\n\
+HjEcMBoGA1UEAwwTQmFuYW5hU2xpY2UgVGVzdCBDQTAgFw0yNjEwMTcwNDA0NTda\n\
+GA8yMTI2MDkyMzA0MDQ1N1owHjEcMBoGA1UEAwwTQmFuYW5hU2xpY2UgVGVzdCBD\n\
+QTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABMs3TV0zK1/zlVlXL2e2GqWmpSGP\n\
+1Cm4Oa8hvKy2wRn/blt9VtQDog7ruRqT1LF0lX/Gt1Yo/Kjrtvb30oSlnsijUzBR\n\
+MB0GA1UdDgQWBBQssXzWDww813YLCwCjWWyUwwVI3TAfBgNVHSMEGDAWgBQssXzW\n\
+Dww813YLCwCjWWyUwwVI3TAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0gA\n\
+MEUCIQCefgvrVSgxrJNZ3kkGNUJ4EwRIl9c9ZNtvR++qZ6yMUwIgb3g1wTdT+gN6\n\
+1aohFhjS24UL1trgcVDFulegU+sU0Z0=\n\
+-----END CERTIFICATE-----\n";
+
+    #[test]
+    fn missing_fields_use_defaults_and_bad_inputs_name_the_field() {
+        let settings: NetworkSettings = serde_json::from_str(r#"{"read_timeout_secs":30}"#).unwrap();
+        assert_eq!(settings.read_timeout_secs, 30);
+        assert_eq!(settings.total_timeout_secs, NetworkSettings::default().total_timeout_secs);
+
+        let with_ca = NetworkSettings { root_certificates_pem: Some(TEST_CA_PEM.to_string()), ..settings.clone() };
+        with_ca.build_client(None).unwrap();
+
+        let bad_pem = NetworkSettings { root_certificates_pem: Some("not a certificate".to_string()), ..settings.clone() };
+        let proxy = ProxySettings { url: "::not a url::".to_string(), username: None };
+        let bad_proxy = NetworkSettings { proxy: Some(proxy), ..settings };
+        for (settings, field) in [(bad_pem, "root_certificates_pem"), (bad_proxy, "proxy.url")] {
+            match settings.build_client(None) {
+                Err(ApiError::BadRequest { field: Some(f), .. }) => assert_eq!(f, field),
+                other => panic!("expected a bad {} error, got {:?}", field, other.map(|_| ())),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_user_agent_with_suffix() {
+        let server = StubServer::start(vec![StubResponse::json(200, "{}")]).await;
+        let settings = NetworkSettings { user_agent_suffix: Some("acme-corp".to_string()), ..Default::default() };
+        settings.build_client(None).unwrap().get(&server.base_url).send().await.unwrap();
+
+        let user_agent = &server.requests()[0].headers["user-agent"];
+        assert!(user_agent.starts_with("BananaSlice/"), "{}", user_agent);
+        assert!(user_agent.ends_with(" acme-corp"), "{}", user_agent);
+    }
+
+    #[tokio::test]
+    async fn stalled_server_times_out() {
+        // Accepts the connection but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let _accepting = tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+
+        let settings = NetworkSettings { read_timeout_secs: 1, total_timeout_secs: 2, ..Default::default() };
+        let error = settings.build_client(None).unwrap().get(&url).send().await.unwrap_err();
+        assert!(error.is_timeout(), "{:?}", error);
+    }
+}
@@ -0,0 +1,419 @@
+// OpenAI-Compatible Images API Module
+// Handles communication with /v1/images/edits (OpenAI, LocalAI, LiteLLM, ...)
+
+use super::error::truncate;
+use super::retry::retry_after;
+use super::{
+    ApiError, AuthStyle, FillRequest, GeneratedImage, GenerationStage, ImageEditProvider, InputLimits, ModelFeedback, ModelInfo,
+    RetryPolicy,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{ImageFormat, Luma, Rgba, RgbaImage};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::io::Cursor;
+
+/// Provider-specific options read from `GenerateRequest.provider_options`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct OpenAiOptions {
+    /// Output size such as "1024x1024" or "auto"; omitted when unset
+    pub size: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImagesResponse {
+    data: Option<Vec<ImageObject>>,
+    error: Option<OpenAiError>,
+}
+
+/// One result; services send `b64_json` or a short-lived `url`
+#[derive(Debug, Deserialize)]
+struct ImageObject {
+    b64_json: Option<String>,
+    url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    #[serde(default)]
+    data: Vec<ModelObject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelObject {
+    id: String,
+}
+
+/// Largest output size of the Images API, in pixels
+const MAX_INPUT_PIXELS: u64 = 1536 * 1024;
+
+/// Image models are recognised by ID, since the list carries no capabilities
+fn is_image_model(id: &str) -> bool {
+    id.starts_with("dall-e") || id.contains("image")
+}
+
+/// gpt-image models always return base64 and reject `response_format`
+fn accepts_response_format(model: &str) -> bool {
+    !model.starts_with("gpt-image")
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiError {
+    message: String,
+    /// Offending request field, when the service names one
+    param: Option<String>,
+}
+
+/// Convert our mask (white = generate, black = keep) to the OpenAI
+/// convention, where fully transparent pixels mark the area to edit.
+/// The mask is resized to the image dimensions if they differ.
+fn convert_mask(mask_base64: &str, width: u32, height: u32) -> Result<Vec<u8>, ApiError> {
+    let bytes = STANDARD
+        .decode(mask_base64)
+        .map_err(|e| ApiError::ParseError(format!("Invalid mask base64: {}", e)))?;
+    let mask = image::load_from_memory(&bytes)
+        .map_err(|e| ApiError::ParseError(format!("Invalid mask image: {}", e)))?;
+
+    let mut luma = mask.to_luma8();
+    if luma.dimensions() != (width, height) {
+        luma = image::imageops::resize(&luma, width, height, image::imageops::FilterType::Triangle);
+    }
+
+    let converted = RgbaImage::from_fn(width, height, |x, y| {
+        let Luma([value]) = *luma.get_pixel(x, y);
+        Rgba([0, 0, 0, 255 - value])
+    });
+
+    let mut buffer = Cursor::new(Vec::new());
+    converted
+        .write_to(&mut buffer, ImageFormat::Png)
+        .map_err(|e| ApiError::ParseError(format!("Failed to encode mask: {}", e)))?;
+    Ok(buffer.into_inner())
+}
+
+/// Classify a failed response, using the service's error message when the body has one
+fn error_from_response(status: StatusCode, response_text: &str, retry_after: Option<u64>) -> ApiError {
+    let (message, param) = match serde_json::from_str::<ImagesResponse>(response_text) {
+        Ok(ImagesResponse { error: Some(error), .. }) => (error.message, error.param),
+        _ => (truncate(response_text, 200).to_string(), None),
+    };
+    ApiError::from_status(status, message, retry_after, param)
+}
+
+fn png_part(bytes: Vec<u8>, file_name: &str) -> Result<Part, ApiError> {
+    Ok(Part::bytes(bytes)
+        .file_name(file_name.to_string())
+        .mime_str("image/png")?)
+}
+
+pub struct OpenAiImagesClient {
+    client: Client,
+    retry: RetryPolicy,
+    auth: AuthStyle,
+    api_key: Option<String>,
+    base_url: String,
+    options: OpenAiOptions,
+}
+
+impl OpenAiImagesClient {
+    pub fn new(api_key: Option<String>, base_url: Option<String>, options: OpenAiOptions) -> Self {
+        Self {
+            client: Client::new(),
+            retry: RetryPolicy::default(),
+            auth: AuthStyle::Bearer,
+            api_key,
+            base_url: base_url.unwrap_or_else(|| "https://api.openai.com/v1".to_string()),
+            options,
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Use a client built from the network settings (timeouts, proxy, root certificates)
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Send the key some other way than bearer auth (e.g. Azure's `api-key` header)
+    pub fn with_auth(mut self, auth: AuthStyle) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Build the multipart form; called once per attempt since forms cannot be cloned
+    fn build_form(
+        &self,
+        request: &FillRequest<'_>,
+        image_bytes: &[u8],
+        mask_bytes: &[u8],
+        reference_bytes: &[Vec<u8>],
+    ) -> Result<Form, ApiError> {
+        let image_field = if reference_bytes.is_empty() { "image" } else { "image[]" };
+        let mut form = Form::new()
+            .text("model", request.model.to_string())
+            .text("prompt", request.prompt.to_string())
+            .text("n", request.candidate_count.to_string())
+            .part(image_field, png_part(image_bytes.to_vec(), "image.png")?)
+            .part("mask", png_part(mask_bytes.to_vec(), "mask.png")?);
+
+        for (i, bytes) in reference_bytes.iter().enumerate() {
+            form = form.part("image[]", png_part(bytes.clone(), &format!("reference_{}.png", i + 1))?);
+        }
+
+        if let Some(size) = &self.options.size {
+            form = form.text("size", size.clone());
+        }
+        if accepts_response_format(request.model) {
+            form = form.text("response_format", "b64_json");
+        }
+        Ok(form)
+    }
+
+    /// GET a models endpoint, returning the body of a successful response
+    async fn get_models(&self, path: &str) -> Result<String, ApiError> {
+        let url = format!("{}/{}", self.base_url.trim_end_matches('/'), path);
+        let mut builder = self.client.get(&url);
+        if let Some(api_key) = &self.api_key {
+            builder = self.auth.apply(builder, api_key);
+        }
+
+        let response = builder.send().await?;
+        let status = response.status();
+        let header_retry_after = retry_after(&response).map(|d| d.as_secs());
+        let response_text = response.text().await?;
+        if status.is_success() {
+            return Ok(response_text);
+        }
+        Err(error_from_response(status, &response_text, header_retry_after))
+    }
+
+    /// Check the key by fetching `model`, or the model list when none is given
+    pub async fn probe(&self, model: Option<&str>) -> Result<(), ApiError> {
+        match model {
+            Some(model) => self.get_models(&format!("models/{}", model)).await?,
+            None => self.get_models("models").await?,
+        };
+        Ok(())
+    }
+
+    /// List the image models served at this endpoint
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, ApiError> {
+        let body = self.get_models("models").await?;
+        let list: ModelList = serde_json::from_str(&body)
+            .map_err(|e| ApiError::ParseError(format!("{}: {}", e, truncate(&body, 200))))?;
+        Ok(list
+            .data
+            .into_iter()
+            .filter(|model| is_image_model(&model.id))
+            .map(|model| ModelInfo::from_id(&model.id))
+            .collect())
+    }
+
+    /// Fetch an image returned by URL, as base64
+    ///
+    /// The URL is pre-signed and may point at another host, so the API key is not sent.
+    async fn download_image(&self, url: &str) -> Result<String, ApiError> {
+        log::info!("Downloading result image");
+        let response = self.client.get(url).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(ApiError::from_status(status, truncate(&text, 200).to_string(), None, None));
+        }
+        Ok(STANDARD.encode(response.bytes().await?))
+    }
+
+    /// Edit the masked region of an image
+    ///
+    /// The source image is sent as `image` (or `image[]` together with any
+    /// reference images) and the converted alpha mask as `mask`.
+    pub async fn generate_fill(&self, request: &FillRequest<'_>) -> Result<Vec<GeneratedImage>, ApiError> {
+        request.progress.stage(GenerationStage::Uploading);
+        let url = format!("{}/images/edits", self.base_url.trim_end_matches('/'));
+
+        let image_bytes = STANDARD
+            .decode(request.image_base64)
+            .map_err(|e| ApiError::ParseError(format!("Invalid image base64: {}", e)))?;
+        let (width, height) = image::load_from_memory(&image_bytes)
+            .map(|img| (img.width(), img.height()))
+            .map_err(|e| ApiError::ParseError(format!("Invalid source image: {}", e)))?;
+        let mask_bytes = convert_mask(request.mask_base64, width, height)?;
+
+        let mut reference_bytes = Vec::with_capacity(request.reference_images.len());
+        for (i, ref_image) in request.reference_images.iter().enumerate() {
+            log::info!("Adding reference image {} ({} bytes)", i + 1, ref_image.len());
+            let bytes = STANDARD
+                .decode(ref_image)
+                .map_err(|e| ApiError::ParseError(format!("Invalid reference image base64: {}", e)))?;
+            reference_bytes.push(bytes);
+        }
+
+        log::info!("Sending request to images/edits: {}", request.model);
+        request.progress.stage(GenerationStage::Waiting);
+
+        let response = self
+            .retry
+            .send(request.progress, || {
+                let form = self.build_form(request, &image_bytes, &mask_bytes, &reference_bytes)?;
+                let mut builder = self.client.post(&url).multipart(form);
+                if let Some(api_key) = &self.api_key {
+                    builder = self.auth.apply(builder, api_key);
+                }
+                Ok(builder)
+            })
+            .await?;
+
+        let status = response.status();
+        let header_retry_after = retry_after(&response).map(|d| d.as_secs());
+        let response_text = response.text().await?;
+
+        log::info!("API response status: {}", status);
+        request.progress.stage(GenerationStage::Decoding);
+
+        if !status.is_success() {
+            log::error!("Images API error: HTTP {}", status);
+            return Err(error_from_response(status, &response_text, header_retry_after));
+        }
+        let images_response: ImagesResponse = serde_json::from_str(&response_text)
+            .map_err(|e| ApiError::ParseError(format!("{}: {}", e, truncate(&response_text, 200))))?;
+
+        if let Some(error) = images_response.error {
+            log::error!("Images API error: {}", error.message);
+            return Err(ApiError::from_status(status, error.message, header_retry_after, error.param));
+        }
+
+        let mut images = Vec::new();
+        for (index, image) in images_response.data.unwrap_or_default().into_iter().enumerate() {
+            let data = match (image.b64_json, image.url) {
+                (Some(data), _) => data,
+                (None, Some(url)) => self.download_image(&url).await?,
+                (None, None) => continue,
+            };
+            images.push(GeneratedImage::new(index as u32, data));
+        }
+
+        if images.is_empty() {
+            return Err(ApiError::NoImageGenerated(ModelFeedback::default()));
+        }
+        Ok(images)
+    }
+}
+
+#[async_trait]
+impl ImageEditProvider for OpenAiImagesClient {
+    fn id(&self) -> &'static str {
+        "openai"
+    }
+
+    async fn inpaint(&self, request: &FillRequest<'_>) -> Result<Vec<GeneratedImage>, ApiError> {
+        self.generate_fill(request).await
+    }
+
+    async fn probe(&self, model: Option<&str>) -> Result<(), ApiError> {
+        OpenAiImagesClient::probe(self, model).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ApiError> {
+        OpenAiImagesClient::list_models(self).await
+    }
+
+    /// Outputs are at most 1536x1024, so larger inputs only cost upload time
+    fn input_limits(&self) -> InputLimits {
+        InputLimits { max_pixels: Some(MAX_INPUT_PIXELS), aspect_ratios: &[] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_server::{NoProgress, StubResponse, StubServer};
+    use image::{DynamicImage, GrayImage};
+
+    fn encode_mask(mask: GrayImage) -> String {
+        let mut buffer = Cursor::new(Vec::new());
+        DynamicImage::ImageLuma8(mask).write_to(&mut buffer, ImageFormat::Png).unwrap();
+        STANDARD.encode(buffer.into_inner())
+    }
+
+    fn encode_png(image: DynamicImage) -> Vec<u8> {
+        let mut buffer = Cursor::new(Vec::new());
+        image.write_to(&mut buffer, ImageFormat::Png).unwrap();
+        buffer.into_inner()
+    }
+
+    async fn fill(server: &StubServer, model: &str) -> Result<Vec<GeneratedImage>, ApiError> {
+        let image = STANDARD.encode(encode_png(DynamicImage::ImageRgba8(RgbaImage::new(4, 4))));
+        let mask = encode_mask(GrayImage::from_pixel(4, 4, Luma([255])));
+        let request = FillRequest {
+            model,
+            prompt: "a red balloon",
+            image_base64: &image,
+            mask_base64: &mask,
+            reference_images: &[],
+            candidate_count: 1,
+            progress: &NoProgress,
+        };
+        let client = OpenAiImagesClient::new(Some("secret".to_string()), Some(server.base_url.clone()), OpenAiOptions::default());
+        client.generate_fill(&request).await
+    }
+
+    #[tokio::test]
+    async fn url_results_are_downloaded() {
+        let png = encode_png(DynamicImage::ImageRgba8(RgbaImage::new(1, 1)));
+        let storage = StubServer::start(vec![StubResponse::bytes(200, "image/png", png.clone())]).await;
+        let body = format!(r#"{{"data":[{{"url":"{}/result.png"}}]}}"#, storage.base_url);
+        let server = StubServer::start(vec![StubResponse::json(200, &body)]).await;
+
+        let images = fill(&server, "dall-e-2").await.unwrap();
+        assert_eq!(images[0].image_base64, STANDARD.encode(&png));
+
+        // The pre-signed URL is fetched without the API key
+        let download = &storage.requests()[0];
+        assert_eq!(download.path, "/result.png");
+        assert!(!download.headers.contains_key("authorization"));
+    }
+
+    #[tokio::test]
+    async fn requests_base64_except_from_gpt_image_models() {
+        let form_for = |model: &'static str| async move {
+            let server = StubServer::start(vec![StubResponse::json(200, r#"{"data":[{"b64_json":"cG5n"}]}"#)]).await;
+            fill(&server, model).await.unwrap();
+            String::from_utf8_lossy(&server.requests()[0].body).into_owned()
+        };
+        assert!(form_for("dall-e-2").await.contains("name=\"response_format\"\r\n\r\nb64_json"));
+        assert!(!form_for("gpt-image-1").await.contains("response_format"));
+    }
+
+    #[tokio::test]
+    async fn failed_status_with_plain_body_is_classified_by_status() {
+        let server = StubServer::start(vec![StubResponse::bytes(401, "text/html", b"<h1>Unauthorized</h1>".to_vec())]).await;
+        let result = fill(&server, "dall-e-2").await;
+        assert!(matches!(result, Err(ApiError::InvalidApiKey(ref m)) if m == "<h1>Unauthorized</h1>"), "{:?}", result);
+    }
+
+    #[test]
+    fn white_mask_areas_become_transparent() {
+        let mask = GrayImage::from_fn(2, 1, |x, _| Luma([if x == 0 { 255 } else { 0 }]));
+        let converted = convert_mask(&encode_mask(mask), 2, 1).unwrap();
+        let img = image::load_from_memory(&converted).unwrap().to_rgba8();
+
+        assert_eq!(img.get_pixel(0, 0)[3], 0);
+        assert_eq!(img.get_pixel(1, 0)[3], 255);
+    }
+
+    #[test]
+    fn mask_is_resized_to_image_dimensions() {
+        let mask = GrayImage::from_pixel(4, 4, Luma([255]));
+        let converted = convert_mask(&encode_mask(mask), 8, 6).unwrap();
+        let img = image::load_from_memory(&converted).unwrap();
+
+        assert_eq!((img.width(), img.height()), (8, 6));
+    }
+}
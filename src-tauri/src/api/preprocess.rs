@@ -0,0 +1,273 @@
+// BananaSlice - Input Preprocessing
+// Fits the crop and mask to a provider's limits before upload and maps the result back afterwards
+
+use super::aspect_ratio::{closest_ratio, RATIO_TOLERANCE};
+use super::ApiError;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GrayImage, ImageFormat, Luma, RgbaImage};
+use serde::Serialize;
+use std::io::Cursor;
+
+/// Input constraints a provider declares
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InputLimits {
+    /// Largest input (width × height) worth uploading; larger inputs are downscaled
+    pub max_pixels: Option<u64>,
+    /// Aspect ratios the provider accepts as `(width, height)`; inputs are padded to
+    /// the closest one. Empty when any ratio works.
+    pub aspect_ratios: &'static [(u32, u32)],
+}
+
+/// How the crop was fitted to the provider's limits, and how to undo it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct InputTransform {
+    /// Crop size as sent by the frontend
+    pub original_width: u32,
+    pub original_height: u32,
+    /// Crop size after downscaling
+    pub scaled_width: u32,
+    pub scaled_height: u32,
+    /// Canvas sent to the provider, with the scaled crop at `offset_x`, `offset_y`
+    pub padded_width: u32,
+    pub padded_height: u32,
+    pub offset_x: u32,
+    pub offset_y: u32,
+}
+
+impl InputTransform {
+    /// Work out the transform for a crop; `None` when it already fits
+    pub fn plan(width: u32, height: u32, limits: &InputLimits) -> Option<Self> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let (w, h) = (width as f64, height as f64);
+
+        // Pad at full resolution first so the cap applies to what is actually sent
+        let (mut padded_w, mut padded_h) = (w, h);
+        if !limits.aspect_ratios.is_empty() {
+            let (rw, rh) = closest_ratio(limits.aspect_ratios, width, height);
+            let target = rw as f64 / rh as f64;
+            if (w / h - target).abs() > RATIO_TOLERANCE {
+                if target > w / h {
+                    padded_w = h * target;
+                } else {
+                    padded_h = w / target;
+                }
+            }
+        }
+
+        let scale = match limits.max_pixels {
+            Some(max) if padded_w * padded_h > max as f64 => (max as f64 / (padded_w * padded_h)).sqrt(),
+            _ => 1.0,
+        };
+        let scaled_width = ((w * scale).round() as u32).max(1);
+        let scaled_height = ((h * scale).round() as u32).max(1);
+        let padded_width = ((padded_w * scale).round() as u32).max(scaled_width);
+        let padded_height = ((padded_h * scale).round() as u32).max(scaled_height);
+
+        let transform = Self {
+            original_width: width,
+            original_height: height,
+            scaled_width,
+            scaled_height,
+            padded_width,
+            padded_height,
+            offset_x: (padded_width - scaled_width) / 2,
+            offset_y: (padded_height - scaled_height) / 2,
+        };
+        let unchanged = (scaled_width, scaled_height) == (width, height)
+            && (padded_width, padded_height) == (width, height);
+        (!unchanged).then_some(transform)
+    }
+
+    fn is_scaled(&self) -> bool {
+        (self.scaled_width, self.scaled_height) != (self.original_width, self.original_height)
+    }
+
+    /// Scale the crop and extend its edges into the padding, so the model sees plausible context
+    pub fn apply_to_image(&self, image: &DynamicImage) -> RgbaImage {
+        let mut rgba = image.to_rgba8();
+        if self.is_scaled() || rgba.dimensions() != (self.original_width, self.original_height) {
+            rgba = imageops::resize(&rgba, self.scaled_width, self.scaled_height, FilterType::Lanczos3);
+        }
+        RgbaImage::from_fn(self.padded_width, self.padded_height, |x, y| {
+            let sx = x.saturating_sub(self.offset_x).min(self.scaled_width - 1);
+            let sy = y.saturating_sub(self.offset_y).min(self.scaled_height - 1);
+            *rgba.get_pixel(sx, sy)
+        })
+    }
+
+    /// Scale the mask and pad it with black, so nothing is generated in the padding
+    pub fn apply_to_mask(&self, mask: &DynamicImage) -> GrayImage {
+        let mut luma = mask.to_luma8();
+        if luma.dimensions() != (self.scaled_width, self.scaled_height) {
+            luma = imageops::resize(&luma, self.scaled_width, self.scaled_height, FilterType::Triangle);
+        }
+        let mut padded = GrayImage::from_pixel(self.padded_width, self.padded_height, Luma([0]));
+        imageops::replace(&mut padded, &luma, self.offset_x as i64, self.offset_y as i64);
+        padded
+    }
+
+    /// Map a generated image (of any resolution) back onto the original crop
+    pub fn invert(&self, output: &DynamicImage) -> RgbaImage {
+        let mut rgba = output.to_rgba8();
+        if rgba.dimensions() != (self.padded_width, self.padded_height) {
+            rgba = imageops::resize(&rgba, self.padded_width, self.padded_height, FilterType::Lanczos3);
+        }
+        let crop = imageops::crop_imm(&rgba, self.offset_x, self.offset_y, self.scaled_width, self.scaled_height)
+            .to_image();
+        if self.is_scaled() {
+            imageops::resize(&crop, self.original_width, self.original_height, FilterType::Lanczos3)
+        } else {
+            crop
+        }
+    }
+}
+
+fn decode(base64_data: &str, what: &str) -> Result<DynamicImage, ApiError> {
+    let bytes = STANDARD
+        .decode(base64_data)
+        .map_err(|e| ApiError::ParseError(format!("Invalid {} base64: {}", what, e)))?;
+    image::load_from_memory(&bytes).map_err(|e| ApiError::ParseError(format!("Invalid {}: {}", what, e)))
+}
+
+fn encode_png(image: DynamicImage) -> Result<String, ApiError> {
+    let mut buffer = Cursor::new(Vec::new());
+    image
+        .write_to(&mut buffer, ImageFormat::Png)
+        .map_err(|e| ApiError::ParseError(format!("Failed to encode PNG: {}", e)))?;
+    Ok(STANDARD.encode(buffer.into_inner()))
+}
+
+/// Inputs ready to upload, with the transform needed to map results back
+pub struct PreparedInputs {
+    pub image_base64: String,
+    pub mask_base64: String,
+    pub reference_images: Vec<String>,
+    pub transform: Option<InputTransform>,
+}
+
+/// Downscale and pad the crop and mask to `limits`; references are only downscaled
+pub fn prepare_inputs(
+    image_base64: &str,
+    mask_base64: &str,
+    reference_images: &[String],
+    limits: &InputLimits,
+) -> Result<PreparedInputs, ApiError> {
+    if limits.max_pixels.is_none() && limits.aspect_ratios.is_empty() {
+        return Ok(PreparedInputs {
+            image_base64: image_base64.to_string(),
+            mask_base64: mask_base64.to_string(),
+            reference_images: reference_images.to_vec(),
+            transform: None,
+        });
+    }
+
+    let image = decode(image_base64, "image")?;
+    let transform = InputTransform::plan(image.width(), image.height(), limits);
+
+    let (image_base64, mask_base64) = match &transform {
+        Some(transform) => {
+            log::info!(
+                "Preprocessing {}x{} crop to {}x{} (padded {}x{})",
+                transform.original_width,
+                transform.original_height,
+                transform.scaled_width,
+                transform.scaled_height,
+                transform.padded_width,
+                transform.padded_height
+            );
+            let mask = decode(mask_base64, "mask")?;
+            (
+                encode_png(DynamicImage::ImageRgba8(transform.apply_to_image(&image)))?,
+                encode_png(DynamicImage::ImageLuma8(transform.apply_to_mask(&mask)))?,
+            )
+        }
+        None => (image_base64.to_string(), mask_base64.to_string()),
+    };
+
+    let reference_limits = InputLimits { max_pixels: limits.max_pixels, aspect_ratios: &[] };
+    let mut references = Vec::with_capacity(reference_images.len());
+    for reference in reference_images {
+        let decoded = decode(reference, "reference image")?;
+        references.push(match InputTransform::plan(decoded.width(), decoded.height(), &reference_limits) {
+            Some(transform) => encode_png(DynamicImage::ImageRgba8(transform.apply_to_image(&decoded)))?,
+            None => reference.clone(),
+        });
+    }
+
+    Ok(PreparedInputs { image_base64, mask_base64, reference_images: references, transform })
+}
+
+/// Map a generated image back onto the original crop
+pub fn restore_output(transform: &InputTransform, image_base64: &str) -> Result<String, ApiError> {
+    let output = decode(image_base64, "generated image")?;
+    encode_png(DynamicImage::ImageRgba8(transform.invert(&output)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const RATIOS: &[(u32, u32)] = &[(16, 9), (1, 1), (9, 16)];
+
+    #[test]
+    fn fitting_inputs_are_left_alone() {
+        let limits = InputLimits { max_pixels: Some(2048 * 2048), aspect_ratios: RATIOS };
+        assert_eq!(InputTransform::plan(800, 800, &limits), None);
+        assert_eq!(InputTransform::plan(1600, 900, &limits), None);
+    }
+
+    #[test]
+    fn pads_to_closest_ratio_then_caps_pixels() {
+        // 2:1 is closest to 16:9, so the crop grows taller before scaling
+        let limits = InputLimits { max_pixels: Some(1600 * 900 / 4), aspect_ratios: RATIOS };
+        let transform = InputTransform::plan(1600, 800, &limits).unwrap();
+
+        assert_eq!((transform.padded_width, transform.padded_height), (800, 450));
+        assert_eq!((transform.scaled_width, transform.scaled_height), (800, 400));
+        assert_eq!((transform.offset_x, transform.offset_y), (0, 25));
+    }
+
+    #[test]
+    fn mask_padding_is_black_and_image_padding_repeats_edges() {
+        let limits = InputLimits { max_pixels: None, aspect_ratios: &[(1, 1)] };
+        let transform = InputTransform::plan(4, 2, &limits).unwrap();
+        assert_eq!((transform.padded_width, transform.padded_height, transform.offset_y), (4, 4, 1));
+
+        let image = RgbaImage::from_fn(4, 2, |x, _| Rgba([x as u8 * 60, 0, 0, 255]));
+        let padded = transform.apply_to_image(&DynamicImage::ImageRgba8(image));
+        assert_eq!(padded.get_pixel(3, 0), padded.get_pixel(3, 1));
+        assert_eq!(padded.get_pixel(3, 3)[0], 180);
+
+        let mask = GrayImage::from_pixel(4, 2, Luma([255]));
+        let padded = transform.apply_to_mask(&DynamicImage::ImageLuma8(mask));
+        assert_eq!(padded.get_pixel(0, 0)[0], 0);
+        assert_eq!(padded.get_pixel(0, 1)[0], 255);
+        assert_eq!(padded.get_pixel(0, 3)[0], 0);
+    }
+
+    #[test]
+    fn inverse_restores_crop_size_and_alignment() {
+        let limits = InputLimits { max_pixels: Some(100 * 100), aspect_ratios: RATIOS };
+        let transform = InputTransform::plan(300, 150, &limits).unwrap();
+
+        // Left half red, right half blue
+        let crop = RgbaImage::from_fn(300, 150, |x, _| {
+            if x < 150 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) }
+        });
+        let sent = transform.apply_to_image(&DynamicImage::ImageRgba8(crop));
+        assert_eq!(sent.dimensions(), (transform.padded_width, transform.padded_height));
+
+        // The model answers at a different resolution than it was sent
+        let output = imageops::resize(&sent, sent.width() * 3, sent.height() * 3, FilterType::Nearest);
+        let restored = transform.invert(&DynamicImage::ImageRgba8(output));
+
+        assert_eq!(restored.dimensions(), (300, 150));
+        assert_eq!(restored.get_pixel(10, 75)[0], 255);
+        assert_eq!(restored.get_pixel(290, 75)[2], 255);
+        assert!(restored.get_pixel(140, 10)[0] > 200 && restored.get_pixel(160, 140)[2] > 200);
+    }
+}
@@ -0,0 +1,216 @@
+// BananaSlice - Image Edit Providers
+// Common interface implemented by every generation backend
+
+use super::{
+    vertex_base_url, ApiError, AuthStyle, Automatic1111Client, ComfyUiClient, InputLimits, ModelInfo, NanoBananaClient,
+    OpenAiImagesClient, RetryPolicy, ServiceAccountKey, TokenSource, VertexOptions,
+};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Provider used when a request does not name one
+pub const DEFAULT_PROVIDER: &str = "gemini";
+
+/// Coarse stages a provider moves through while handling a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GenerationStage {
+    /// Encoding and sending the inputs
+    Uploading,
+    /// Inputs sent; waiting for the model to produce output
+    Waiting,
+    /// Output received; extracting images from the response
+    Decoding,
+}
+
+/// Receives stage updates from a provider
+pub trait ProgressSink: Send + Sync {
+    fn stage(&self, stage: GenerationStage);
+
+    /// A request failed transiently and attempt number `attempt` starts after `delay`
+    fn retry(&self, _attempt: u32, _delay: Duration, _reason: &str) {}
+}
+
+/// Inputs for a single inpainting call
+#[derive(Clone, Copy)]
+pub struct FillRequest<'a> {
+    /// Model alias or provider-specific model ID
+    pub model: &'a str,
+    /// Text description of what to generate
+    pub prompt: &'a str,
+    /// The cropped source image as base64 PNG
+    pub image_base64: &'a str,
+    /// The mask as base64 PNG (white = generate, black = keep)
+    pub mask_base64: &'a str,
+    /// Optional reference images to guide generation
+    pub reference_images: &'a [&'a str],
+    /// Number of variants to generate (at least 1)
+    pub candidate_count: u32,
+    /// Where to report stage changes
+    pub progress: &'a dyn ProgressSink,
+}
+
+/// A provider's safety classification for one harm category
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafetyRating {
+    pub category: String,
+    pub probability: String,
+    /// True when this category caused the output to be withheld
+    #[serde(default)]
+    pub blocked: bool,
+}
+
+/// What the model reported besides images: text, stop reasons and safety verdicts
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ModelFeedback {
+    /// Text parts the model returned, joined with newlines
+    pub model_text: Option<String>,
+    pub finish_reason: Option<String>,
+    /// Why the prompt itself was rejected before any candidate was generated
+    pub block_reason: Option<String>,
+    pub safety_ratings: Vec<SafetyRating>,
+}
+
+/// One image returned by a provider
+#[derive(Debug, Clone, Serialize)]
+pub struct GeneratedImage {
+    /// Position of the candidate in the provider response
+    pub index: u32,
+    pub image_base64: String,
+    /// Why the provider stopped generating this candidate, when reported
+    pub finish_reason: Option<String>,
+    /// Text the model returned alongside the image
+    pub model_text: Option<String>,
+    pub safety_ratings: Vec<SafetyRating>,
+}
+
+impl GeneratedImage {
+    /// An image with no accompanying model feedback
+    pub fn new(index: u32, image_base64: String) -> Self {
+        Self {
+            index,
+            image_base64,
+            finish_reason: None,
+            model_text: None,
+            safety_ratings: Vec::new(),
+        }
+    }
+
+    /// Feedback attached to this image
+    pub fn feedback(&self) -> ModelFeedback {
+        ModelFeedback {
+            model_text: self.model_text.clone(),
+            finish_reason: self.finish_reason.clone(),
+            block_reason: None,
+            safety_ratings: self.safety_ratings.clone(),
+        }
+    }
+}
+
+/// Connection settings handed to a provider when it is created
+#[derive(Debug, Default, Clone)]
+pub struct ProviderConfig {
+    pub api_key: Option<String>,
+    pub base_url: Option<String>,
+    /// How the key is sent; each provider falls back to its own convention
+    pub auth: Option<AuthStyle>,
+    /// Provider-specific options, parsed by each provider
+    pub options: serde_json::Value,
+    /// Retry policy for transient HTTP failures
+    pub retry: RetryPolicy,
+    /// HTTP client built from the network settings
+    pub client: Client,
+}
+
+/// A backend that can inpaint a masked region of an image
+#[async_trait]
+pub trait ImageEditProvider: Send + Sync {
+    /// Stable identifier used in `GenerateRequest.provider`
+    fn id(&self) -> &'static str;
+
+    /// Generate content for the masked region, returning every candidate image
+    async fn inpaint(&self, request: &FillRequest<'_>) -> Result<Vec<GeneratedImage>, ApiError>;
+
+    /// Cheap request that checks the credential (and `model`, where the provider can) without generating
+    async fn probe(&self, model: Option<&str>) -> Result<(), ApiError>;
+
+    /// Image-capable models this provider can use; empty when the model is chosen elsewhere
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ApiError>;
+
+    /// Size and aspect-ratio constraints applied to inputs before `inpaint`
+    fn input_limits(&self) -> InputLimits {
+        InputLimits::default()
+    }
+}
+
+/// Parse provider-specific options, falling back to defaults when none are given
+fn parse_options<T: DeserializeOwned + Default>(options: serde_json::Value) -> Result<T, ApiError> {
+    if options.is_null() {
+        return Ok(T::default());
+    }
+    serde_json::from_value(options).map_err(|e| ApiError::ParseError(format!("Invalid provider options: {}", e)))
+}
+
+/// Create the provider registered under `id`
+pub fn create_provider(id: &str, config: ProviderConfig) -> Result<Box<dyn ImageEditProvider>, ApiError> {
+    match id {
+        "gemini" => {
+            let api_key = config.api_key.ok_or(ApiError::ApiKeyMissing)?;
+            let client = match config.base_url {
+                Some(base_url) if !base_url.is_empty() => NanoBananaClient::with_base_url(api_key, base_url),
+                _ => NanoBananaClient::new(api_key),
+            };
+            let client = match config.auth {
+                Some(auth) => client.with_auth(auth),
+                None => client,
+            };
+            Ok(Box::new(client.with_retry_policy(config.retry).with_http_client(config.client)))
+        }
+        "vertex" => {
+            let options: VertexOptions = parse_options(config.options)?;
+            let key = match (&options.credentials_path, config.api_key) {
+                (Some(path), _) => ServiceAccountKey::from_file(path)?,
+                (None, Some(json)) => ServiceAccountKey::from_json(&json)?,
+                (None, None) => return Err(ApiError::ApiKeyMissing),
+            };
+            let tokens = TokenSource::new(key).with_http_client(config.client.clone());
+            let project = options
+                .project_id
+                .or_else(|| tokens.project_id().map(str::to_string))
+                .ok_or_else(|| ApiError::BadRequest {
+                    message: "Vertex AI requires a project ID".to_string(),
+                    field: Some("project_id".to_string()),
+                })?;
+            let endpoint = config.base_url.filter(|url| !url.is_empty());
+            let base_url = vertex_base_url(endpoint.as_deref(), &project, &options.location);
+            let client = NanoBananaClient::vertex(tokens, base_url).with_retry_policy(config.retry);
+            Ok(Box::new(client.with_http_client(config.client)))
+        }
+        "openai" => {
+            let base_url = config.base_url.filter(|url| !url.is_empty());
+            let options = parse_options(config.options)?;
+            let client = OpenAiImagesClient::new(config.api_key, base_url, options);
+            let client = match config.auth {
+                Some(auth) => client.with_auth(auth),
+                None => client,
+            };
+            Ok(Box::new(client.with_retry_policy(config.retry).with_http_client(config.client)))
+        }
+        "automatic1111" => {
+            let base_url = config.base_url.filter(|url| !url.is_empty());
+            let options = parse_options(config.options)?;
+            let client = Automatic1111Client::new(base_url, options).with_retry_policy(config.retry);
+            Ok(Box::new(client.with_http_client(config.client)))
+        }
+        "comfyui" => {
+            let base_url = config.base_url.filter(|url| !url.is_empty());
+            let options = parse_options(config.options)?;
+            let client = ComfyUiClient::new(base_url, options).with_retry_policy(config.retry);
+            Ok(Box::new(client.with_http_client(config.client)))
+        }
+        other => Err(ApiError::UnknownProvider(other.to_string())),
+    }
+}
@@ -0,0 +1,241 @@
+// Retry Policy
+// Exponential backoff for transient provider failures (429, 5xx, dropped connections)
+
+use super::{ApiError, ProgressSink};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// How failed requests are retried
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one (1 disables retries)
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled on every further attempt
+    pub base_delay_ms: u64,
+    /// Upper bound for any single delay; a longer `Retry-After` stops retrying
+    pub max_delay_ms: u64,
+    /// Random spread applied to computed delays, as a fraction (0.2 = ±20%)
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 1000,
+            max_delay_ms: 30_000,
+            jitter: 0.2,
+        }
+    }
+}
+
+/// Statuses that indicate a transient failure worth retrying
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504)
+}
+
+/// Connection failures and resets, as opposed to timeouts or bad requests
+fn is_retryable_error(error: &reqwest::Error) -> bool {
+    if error.is_connect() {
+        return true;
+    }
+
+    let mut source = std::error::Error::source(error);
+    while let Some(inner) = source {
+        if let Some(io) = inner.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            );
+        }
+        source = inner.source();
+    }
+    false
+}
+
+/// Parse a delta-seconds `Retry-After` header (HTTP dates fall back to backoff)
+pub(super) fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Random value in [0, 1)
+fn random_unit() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry` (1-based), honoring `Retry-After` when given
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        let max = Duration::from_millis(self.max_delay_ms);
+        if let Some(wait) = retry_after {
+            return wait.min(max);
+        }
+
+        let exponential = self.base_delay_ms as f64 * 2f64.powi(retry.saturating_sub(1) as i32);
+        let spread = 1.0 + self.jitter.clamp(0.0, 1.0) * (random_unit() * 2.0 - 1.0);
+        Duration::from_millis((exponential * spread) as u64).min(max)
+    }
+
+    /// Send a request, retrying transient failures
+    ///
+    /// `build` is called for every attempt because multipart bodies cannot be
+    /// cloned. When retries run out on a retryable status, the last response
+    /// is returned so the provider can report the service's own error message.
+    /// A `Retry-After` longer than `max_delay_ms` also ends the retries: a 429
+    /// becomes `QuotaExceeded` carrying the wait, so the user can see how long it is.
+    pub async fn send<F>(&self, progress: &dyn ProgressSink, build: F) -> Result<Response, ApiError>
+    where
+        F: Fn() -> Result<RequestBuilder, ApiError>,
+    {
+        let max_attempts = self.max_attempts.max(1);
+        let mut attempt = 1;
+
+        loop {
+            let (reason, wait) = match build()?.send().await {
+                Ok(response) if is_retryable_status(response.status()) && attempt < max_attempts => {
+                    let wait = retry_after(&response);
+                    match wait {
+                        Some(wait) if wait > Duration::from_millis(self.max_delay_ms) => {
+                            return Self::too_long_to_wait(response, wait);
+                        }
+                        _ => (format!("HTTP {}", response.status()), wait),
+                    }
+                }
+                Ok(response) => return Ok(response),
+                Err(e) if is_retryable_error(&e) && attempt < max_attempts => (e.to_string(), None),
+                Err(e) => return Err(e.into()),
+            };
+
+            let delay = self.delay(attempt, wait);
+            log::warn!(
+                "Attempt {}/{} failed ({}); retrying in {}ms",
+                attempt, max_attempts, reason, delay.as_millis()
+            );
+            progress.retry(attempt + 1, delay, &reason);
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Give up on a response whose `Retry-After` is past the longest delay we wait for
+    fn too_long_to_wait(response: Response, wait: Duration) -> Result<Response, ApiError> {
+        log::warn!("HTTP {} asks to retry in {}s; not waiting that long", response.status(), wait.as_secs());
+        if response.status() != StatusCode::TOO_MANY_REQUESTS {
+            return Ok(response);
+        }
+        Err(ApiError::QuotaExceeded {
+            message: format!("Rate limited; the service asks to wait {} seconds before retrying", wait.as_secs()),
+            retry_after: Some(wait.as_secs()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_server::{NoProgress, StubResponse, StubServer};
+    use reqwest::Client;
+
+    fn fast_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy { max_attempts, base_delay_ms: 1, max_delay_ms: 50, jitter: 0.0 }
+    }
+
+    #[test]
+    fn delay_doubles_and_is_capped() {
+        let policy = RetryPolicy { max_attempts: 5, base_delay_ms: 100, max_delay_ms: 350, jitter: 0.0 };
+        assert_eq!(policy.delay(1, None), Duration::from_millis(100));
+        assert_eq!(policy.delay(2, None), Duration::from_millis(200));
+        assert_eq!(policy.delay(3, None), Duration::from_millis(350));
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(60))), Duration::from_millis(350));
+    }
+
+    #[tokio::test]
+    async fn retries_transient_statuses_until_success() {
+        let server = StubServer::start(vec![
+            StubResponse::json(503, "{}"),
+            StubResponse::json(429, "{}").with_header("Retry-After", "0"),
+            StubResponse::json(200, r#"{"ok":true}"#),
+        ])
+        .await;
+        let client = Client::new();
+        let url = format!("{}/generate", server.base_url);
+
+        let response = fast_policy(3).send(&NoProgress, || Ok(client.post(&url))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn returns_last_response_when_attempts_run_out() {
+        let server = StubServer::start(vec![StubResponse::json(500, "{}"), StubResponse::json(500, "{}")]).await;
+        let client = Client::new();
+        let url = format!("{}/generate", server.base_url);
+
+        let response = fast_policy(2).send(&NoProgress, || Ok(client.post(&url))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn long_retry_after_is_reported_instead_of_capped() {
+        let server = StubServer::start(vec![
+            StubResponse::json(429, "{}").with_header("Retry-After", "3600"),
+            StubResponse::json(200, "{}"),
+        ])
+        .await;
+        let client = Client::new();
+        let url = format!("{}/generate", server.base_url);
+
+        let result = fast_policy(3).send(&NoProgress, || Ok(client.post(&url))).await;
+        assert!(matches!(result, Err(ApiError::QuotaExceeded { retry_after: Some(3600), .. })), "{:?}", result);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let server = StubServer::start(vec![StubResponse::json(400, "{}"), StubResponse::json(200, "{}")]).await;
+        let client = Client::new();
+        let url = format!("{}/generate", server.base_url);
+
+        let response = fast_policy(3).send(&NoProgress, || Ok(client.post(&url))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_refused_connections() {
+        // Bind and drop a listener to get a port nothing is listening on
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let client = Client::new();
+        let url = format!("http://127.0.0.1:{}/generate", port);
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+
+        let result = fast_policy(3)
+            .send(&NoProgress, || {
+                attempts.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                Ok(client.post(&url))
+            })
+            .await;
+        assert!(matches!(result, Err(ApiError::RequestFailed(_))));
+        assert_eq!(attempts.into_inner(), 3);
+    }
+}
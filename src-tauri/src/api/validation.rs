@@ -0,0 +1,109 @@
+// BananaSlice - Credential Validation
+// Typed outcome of probing a provider with a key before it is saved or used
+
+use super::ApiError;
+use serde::Serialize;
+
+/// Result of `ImageEditProvider::probe`, as reported to Settings
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum KeyValidation {
+    Valid,
+    /// The provider rejected the key (or none was configured)
+    Invalid { message: String },
+    /// The key is accepted but has no quota left; `retry_after` is in seconds
+    QuotaExhausted { message: String, retry_after: Option<u64> },
+    /// The provider could not be reached, so the key is unverified
+    NetworkUnreachable { message: String },
+    /// The key is accepted but cannot use the requested model
+    ModelNotAvailable { model: String, message: String },
+    /// Any other failure; the key is unverified
+    Unknown { message: String },
+}
+
+impl KeyValidation {
+    /// Classify a probe result; `model` is the model the probe asked about, if any
+    pub fn from_probe(result: Result<(), ApiError>, model: Option<&str>) -> Self {
+        let error = match result {
+            Ok(()) => return KeyValidation::Valid,
+            Err(error) => error,
+        };
+        let message = error.to_string();
+        match error {
+            ApiError::ApiKeyMissing | ApiError::InvalidApiKey(_) => KeyValidation::Invalid { message },
+            ApiError::QuotaExceeded { retry_after, .. } => KeyValidation::QuotaExhausted { message, retry_after },
+            ApiError::RequestFailed(_) | ApiError::Timeout(_) => KeyValidation::NetworkUnreachable { message },
+            ApiError::BadRequest { .. } if model.is_some() => KeyValidation::ModelNotAvailable {
+                model: model.unwrap_or_default().to_string(),
+                message,
+            },
+            _ => KeyValidation::Unknown { message },
+        }
+    }
+
+    /// Whether the provider authenticated the key, even if it cannot generate right now
+    pub fn key_accepted(&self) -> bool {
+        matches!(
+            self,
+            KeyValidation::Valid | KeyValidation::QuotaExhausted { .. } | KeyValidation::ModelNotAvailable { .. }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_server::{StubResponse, StubServer};
+    use crate::api::NanoBananaClient;
+
+    #[test]
+    fn classifies_probe_errors() {
+        let quota = ApiError::QuotaExceeded { message: "out of quota".to_string(), retry_after: Some(30) };
+        assert!(matches!(
+            KeyValidation::from_probe(Err(quota), None),
+            KeyValidation::QuotaExhausted { retry_after: Some(30), .. }
+        ));
+        assert!(matches!(
+            KeyValidation::from_probe(Err(ApiError::InvalidApiKey("bad".to_string())), None),
+            KeyValidation::Invalid { .. }
+        ));
+
+        let not_found = || ApiError::BadRequest { message: "not found".to_string(), field: None };
+        assert_eq!(
+            KeyValidation::from_probe(Err(not_found()), Some("nano-banana-pro")),
+            KeyValidation::ModelNotAvailable {
+                model: "nano-banana-pro".to_string(),
+                message: "Invalid request: not found".to_string(),
+            }
+        );
+        assert!(matches!(KeyValidation::from_probe(Err(not_found()), None), KeyValidation::Unknown { .. }));
+        assert!(!KeyValidation::Unknown { message: String::new() }.key_accepted());
+    }
+
+    #[tokio::test]
+    async fn unreachable_provider_is_a_network_failure() {
+        // Bind and drop a listener so the port is known to be closed
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let client = NanoBananaClient::with_base_url("key".to_string(), base_url);
+        let validation = KeyValidation::from_probe(client.probe(None).await, None);
+        assert!(matches!(validation, KeyValidation::NetworkUnreachable { .. }), "got {:?}", validation);
+    }
+
+    #[tokio::test]
+    async fn rejected_key_is_invalid() {
+        let server = StubServer::start(vec![StubResponse::json(
+            400,
+            r#"{"error":{"code":400,"message":"API key not valid.","status":"INVALID_ARGUMENT",
+                "details":[{"@type":"type.googleapis.com/google.rpc.ErrorInfo","reason":"API_KEY_INVALID"}]}}"#,
+        )])
+        .await;
+        let client = NanoBananaClient::with_base_url("bad".to_string(), server.base_url.clone());
+
+        let validation = KeyValidation::from_probe(client.probe(None).await, None);
+        assert!(!validation.key_accepted());
+        assert!(matches!(validation, KeyValidation::Invalid { .. }));
+    }
+}
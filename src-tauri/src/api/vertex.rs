@@ -0,0 +1,347 @@
+// Vertex AI Service Accounts
+// Mints and caches OAuth2 access tokens from a service-account key (JWT bearer grant)
+
+use super::error::truncate;
+use super::ApiError;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const JWT_BEARER_GRANT: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+
+/// Lifetime requested for each signed assertion (Google's maximum)
+const ASSERTION_LIFETIME: Duration = Duration::from_secs(3600);
+/// Tokens are refreshed this long before they expire
+const REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+fn default_location() -> String {
+    "us-central1".to_string()
+}
+
+/// Provider-specific options read from `GenerateRequest.provider_options`
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct VertexOptions {
+    /// Google Cloud project; defaults to the key's `project_id`
+    pub project_id: Option<String>,
+    /// Region such as "us-central1", or "global"
+    #[serde(default = "default_location")]
+    pub location: String,
+    /// Path to a service-account JSON key; otherwise the stored secret holds the JSON itself
+    pub credentials_path: Option<String>,
+}
+
+impl Default for VertexOptions {
+    fn default() -> Self {
+        Self {
+            project_id: None,
+            location: default_location(),
+            credentials_path: None,
+        }
+    }
+}
+
+/// The fields we need from a downloaded service-account JSON key
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServiceAccountKey {
+    pub client_email: String,
+    pub private_key: String,
+    #[serde(default)]
+    pub private_key_id: Option<String>,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub token_uri: Option<String>,
+}
+
+impl ServiceAccountKey {
+    pub fn from_json(json: &str) -> Result<Self, ApiError> {
+        serde_json::from_str(json)
+            .map_err(|e| ApiError::ParseError(format!("Invalid service-account key: {}", e)))
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, ApiError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| ApiError::ParseError(format!("Cannot read service-account key {}: {}", path, e)))?;
+        Self::from_json(&json)
+    }
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    expires_in: Option<u64>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+#[derive(Clone)]
+struct CachedToken {
+    access_token: String,
+    expires_at: SystemTime,
+}
+
+/// Tokens shared by every client, keyed by account and token endpoint,
+/// since providers are rebuilt for each generation
+fn token_cache() -> &'static Mutex<HashMap<String, CachedToken>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, CachedToken>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Produces access tokens for one service account
+pub struct TokenSource {
+    client: Client,
+    key: ServiceAccountKey,
+    token_uri: String,
+}
+
+impl TokenSource {
+    pub fn new(key: ServiceAccountKey) -> Self {
+        let token_uri = key.token_uri.clone().unwrap_or_else(|| DEFAULT_TOKEN_URI.to_string());
+        Self { client: Client::new(), key, token_uri }
+    }
+
+    /// Use a client built from the network settings (timeouts, proxy, root certificates)
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn project_id(&self) -> Option<&str> {
+        self.key.project_id.as_deref()
+    }
+
+    fn cache_key(&self) -> String {
+        format!("{}|{}", self.key.client_email, self.token_uri)
+    }
+
+    /// Sign a JWT asserting the service account's identity
+    fn signed_assertion(&self, now: SystemTime) -> Result<String, ApiError> {
+        let iat = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let claims = Claims {
+            iss: &self.key.client_email,
+            scope: CLOUD_PLATFORM_SCOPE,
+            aud: &self.token_uri,
+            iat,
+            exp: iat + ASSERTION_LIFETIME.as_secs(),
+        };
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = self.key.private_key_id.clone();
+
+        let encoding_key = EncodingKey::from_rsa_pem(self.key.private_key.as_bytes())
+            .map_err(|e| ApiError::InvalidApiKey(format!("Invalid service-account private key: {}", e)))?;
+        jsonwebtoken::encode(&header, &claims, &encoding_key)
+            .map_err(|e| ApiError::InvalidApiKey(format!("Failed to sign token request: {}", e)))
+    }
+
+    /// A valid access token, refreshed when missing or close to expiry
+    pub async fn access_token(&self) -> Result<String, ApiError> {
+        let cache_key = self.cache_key();
+        let now = SystemTime::now();
+        if let Some(cached) = token_cache().lock().unwrap().get(&cache_key) {
+            if cached.expires_at > now + REFRESH_MARGIN {
+                return Ok(cached.access_token.clone());
+            }
+        }
+
+        log::info!("Requesting Vertex AI access token for {}", self.key.client_email);
+        let assertion = self.signed_assertion(now)?;
+        let response = self
+            .client
+            .post(&self.token_uri)
+            .form(&[("grant_type", JWT_BEARER_GRANT), ("assertion", assertion.as_str())])
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body = response.text().await?;
+        let token: TokenResponse = serde_json::from_str(&body)
+            .map_err(|e| ApiError::ParseError(format!("{}: {}", e, truncate(&body, 200))))?;
+
+        let access_token = match token.access_token {
+            Some(access_token) if status.is_success() => access_token,
+            _ => {
+                let message = token
+                    .error_description
+                    .or(token.error)
+                    .unwrap_or_else(|| format!("Token exchange failed ({})", status));
+                // A rejected assertion is a credentials problem, whatever the status
+                return Err(match status.as_u16() {
+                    400 | 401 | 403 => ApiError::InvalidApiKey(message),
+                    _ => ApiError::from_status(status, message, None, None),
+                });
+            }
+        };
+
+        let lifetime = Duration::from_secs(token.expires_in.unwrap_or(ASSERTION_LIFETIME.as_secs()));
+        let cached = CachedToken { access_token: access_token.clone(), expires_at: now + lifetime };
+        token_cache().lock().unwrap().insert(cache_key, cached);
+        Ok(access_token)
+    }
+}
+
+/// Base URL for Gemini models published on Vertex AI in `project`/`location`
+///
+/// `endpoint` overrides the regional host, e.g. for private service connect.
+pub fn vertex_base_url(endpoint: Option<&str>, project: &str, location: &str) -> String {
+    let host = match endpoint {
+        Some(endpoint) => endpoint.trim_end_matches('/').to_string(),
+        None if location == "global" => "https://aiplatform.googleapis.com".to_string(),
+        None => format!("https://{}-aiplatform.googleapis.com", location),
+    };
+    format!("{}/v1/projects/{}/locations/{}/publishers/google", host, project, location)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_server::{NoProgress, StubResponse, StubServer};
+    use crate::api::{FillRequest, ImageEditProvider, NanoBananaClient};
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use std::sync::OnceLock;
+
+    /// Throwaway 2048-bit key, generated once per test run (RS256 signing needs at least 2048 bits)
+    fn test_private_key() -> &'static str {
+        static KEY: OnceLock<String> = OnceLock::new();
+        KEY.get_or_init(|| {
+            use rsa::pkcs8::{EncodePrivateKey, LineEnding};
+            let key = rsa::RsaPrivateKey::new(&mut rsa::rand_core::OsRng, 2048).unwrap();
+            key.to_pkcs8_pem(LineEnding::LF).unwrap().to_string()
+        })
+    }
+
+    fn token_source(email: &str, server: &StubServer) -> TokenSource {
+        let key = ServiceAccountKey {
+            client_email: email.to_string(),
+            private_key: test_private_key().to_string(),
+            private_key_id: Some("key-1".to_string()),
+            project_id: Some("banana-project".to_string()),
+            token_uri: Some(format!("{}/token", server.base_url)),
+        };
+        TokenSource::new(key)
+    }
+
+    fn form_value(body: &[u8], name: &str) -> String {
+        String::from_utf8_lossy(body)
+            .split('&')
+            .find_map(|pair| pair.strip_prefix(&format!("{}=", name)).map(str::to_string))
+            .unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn exchanges_signed_assertion_and_caches_token() {
+        let server = StubServer::start(vec![StubResponse::json(
+            200,
+            r#"{"access_token":"ya29.first","expires_in":3600,"token_type":"Bearer"}"#,
+        )])
+        .await;
+        let tokens = token_source("cache@banana-project.iam.gserviceaccount.com", &server);
+
+        assert_eq!(tokens.access_token().await.unwrap(), "ya29.first");
+        assert_eq!(tokens.access_token().await.unwrap(), "ya29.first");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(form_value(&requests[0].body, "grant_type"), "urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Ajwt-bearer");
+
+        let assertion = form_value(&requests[0].body, "assertion");
+        let segments: Vec<&str> = assertion.split('.').collect();
+        assert_eq!(segments.len(), 3);
+        let header: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(segments[0]).unwrap()).unwrap();
+        let claims: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(segments[1]).unwrap()).unwrap();
+        assert_eq!(header["alg"], "RS256");
+        assert_eq!(header["kid"], "key-1");
+        assert_eq!(claims["iss"], "cache@banana-project.iam.gserviceaccount.com");
+        assert_eq!(claims["scope"], CLOUD_PLATFORM_SCOPE);
+        assert_eq!(claims["aud"], format!("{}/token", server.base_url));
+    }
+
+    #[tokio::test]
+    async fn refreshes_tokens_close_to_expiry() {
+        let server = StubServer::start(vec![
+            StubResponse::json(200, r#"{"access_token":"ya29.short","expires_in":60}"#),
+            StubResponse::json(200, r#"{"access_token":"ya29.fresh","expires_in":3600}"#),
+        ])
+        .await;
+        let tokens = token_source("refresh@banana-project.iam.gserviceaccount.com", &server);
+
+        assert_eq!(tokens.access_token().await.unwrap(), "ya29.short");
+        assert_eq!(tokens.access_token().await.unwrap(), "ya29.fresh");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rejected_assertion_is_an_invalid_key() {
+        let server = StubServer::start(vec![StubResponse::json(
+            400,
+            r#"{"error":"invalid_grant","error_description":"Invalid JWT Signature."}"#,
+        )])
+        .await;
+        let tokens = token_source("rejected@banana-project.iam.gserviceaccount.com", &server);
+
+        let result = tokens.access_token().await;
+        assert!(matches!(result, Err(ApiError::InvalidApiKey(message)) if message == "Invalid JWT Signature."));
+    }
+
+    #[tokio::test]
+    async fn gemini_client_calls_vertex_with_bearer_token() {
+        let server = StubServer::start(vec![
+            StubResponse::json(200, r#"{"access_token":"ya29.vertex","expires_in":3600}"#),
+            StubResponse::json(
+                200,
+                r#"{"candidates":[{"content":{"parts":[{"inlineData":{"mimeType":"image/png","data":"cG5n"}}]}}]}"#,
+            ),
+        ])
+        .await;
+        let tokens = token_source("vertex@banana-project.iam.gserviceaccount.com", &server);
+        let base_url = vertex_base_url(Some(&server.base_url), "banana-project", "us-central1");
+        let client = NanoBananaClient::vertex(tokens, base_url);
+
+        let request = FillRequest {
+            model: "nano-banana",
+            prompt: "a red balloon",
+            image_base64: "aW1hZ2U=",
+            mask_base64: "bWFzaw==",
+            reference_images: &[],
+            candidate_count: 1,
+            progress: &NoProgress,
+        };
+        let images = client.inpaint(&request).await.unwrap();
+        assert_eq!(images[0].image_base64, "cG5n");
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/token");
+        assert_eq!(
+            requests[1].path,
+            "/v1/projects/banana-project/locations/us-central1/publishers/google/models/gemini-2.5-flash-image:generateContent"
+        );
+        assert_eq!(requests[1].headers["authorization"], "Bearer ya29.vertex");
+        assert_eq!(client.id(), "vertex");
+    }
+
+    #[test]
+    fn builds_regional_and_global_urls() {
+        assert_eq!(
+            vertex_base_url(None, "p", "us-central1"),
+            "https://us-central1-aiplatform.googleapis.com/v1/projects/p/locations/us-central1/publishers/google"
+        );
+        assert_eq!(
+            vertex_base_url(None, "p", "global"),
+            "https://aiplatform.googleapis.com/v1/projects/p/locations/global/publishers/google"
+        );
+    }
+}
@@ -0,0 +1,18 @@
+// BananaSlice - Aspect Ratio Commands
+// Fits a selection to a supported aspect ratio before generating with references
+
+use crate::api::{calculate_adjustment, AspectRatioAdjustment, SelectionRect};
+
+/// Work out how a selection should grow to the closest supported aspect ratio
+///
+/// Pass the canvas size to keep the growth on the canvas; whatever does not fit is
+/// reported as padding.
+#[tauri::command]
+pub fn calculate_aspect_ratio_adjustment(
+    selection: SelectionRect,
+    canvas_width: Option<u32>,
+    canvas_height: Option<u32>,
+) -> Result<AspectRatioAdjustment, String> {
+    calculate_adjustment(selection, canvas_width, canvas_height)
+        .ok_or_else(|| format!("Selection is too small: {}x{}", selection.width, selection.height))
+}
@@ -1,10 +1,15 @@
 // BananaSlice - Compositing Commands
 // Handles compositing generated patches back onto the original image
 
+use crate::compositor::{
+    apply_mask, draw_layer, flatten, layer_mask, Affine, BlendMode, LayerTransform, MaskShape, Point, ShapeType,
+};
 use base64::{engine::general_purpose::STANDARD, Engine};
-use image::{DynamicImage, ImageFormat};
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageFormat, Rgb};
 use image::imageops::FilterType;
-use serde::{Deserialize, Serialize};
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Deserializer, Serialize};
 use std::io::Cursor;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -13,10 +18,10 @@ pub struct CompositeRequest {
     pub base_image_base64: String,
     /// The generated patch as base64
     pub patch_image_base64: String,
-    /// X position to place the patch
-    pub x: u32,
-    /// Y position to place the patch
-    pub y: u32,
+    /// X position to place the patch; may be negative or fractional
+    pub x: f64,
+    /// Y position to place the patch; may be negative or fractional
+    pub y: f64,
     /// Target width to resize the patch to (selection width)
     pub target_width: u32,
     /// Target height to resize the patch to (selection height)
@@ -43,7 +48,9 @@ fn decode_image(base64_data: &str) -> Result<DynamicImage, String> {
 }
 
 /// Encode DynamicImage to base64
-fn encode_image(img: &DynamicImage, format: &str) -> Result<String, String> {
+///
+/// `quality` (1-100) only applies to JPEG; WebP is always written lossless.
+fn encode_image(img: &DynamicImage, format: &str, quality: Option<u8>) -> Result<String, String> {
     let mut buffer = Cursor::new(Vec::new());
     
     let image_format = match format.to_lowercase().as_str() {
@@ -52,8 +59,22 @@ fn encode_image(img: &DynamicImage, format: &str) -> Result<String, String> {
         _ => ImageFormat::Png,
     };
     
-    img.write_to(&mut buffer, image_format)
-        .map_err(|e| format!("Failed to encode image: {}", e))?;
+    // JPEG has no alpha channel, so transparent areas are flattened onto white
+    let flattened;
+    let img = if image_format == ImageFormat::Jpeg && img.color().has_alpha() {
+        flattened = DynamicImage::ImageRgb8(flatten(&img.to_rgba8(), Rgb([255, 255, 255])));
+        &flattened
+    } else {
+        img
+    };
+
+    match quality {
+        Some(quality) if image_format == ImageFormat::Jpeg => {
+            img.write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, quality.clamp(1, 100)))
+        }
+        _ => img.write_to(&mut buffer, image_format),
+    }
+    .map_err(|e| format!("Failed to encode image: {}", e))?;
     
     Ok(STANDARD.encode(buffer.into_inner()))
 }
@@ -106,22 +127,11 @@ pub fn composite_patch(request: CompositeRequest) -> CompositeResponse {
     let patch_rgba = resized_patch.to_rgba8();
     
     // Composite the patch onto the base at (x, y)
-    for (px, py, pixel) in patch_rgba.enumerate_pixels() {
-        let target_x = request.x + px;
-        let target_y = request.y + py;
-        
-        // Check bounds
-        if target_x < result.width() && target_y < result.height() {
-            // Alpha blending
-            let base_pixel = result.get_pixel(target_x, target_y);
-            let blended = alpha_blend(base_pixel, pixel);
-            result.put_pixel(target_x, target_y, blended);
-        }
-    }
+    draw_layer(&mut result, &patch_rgba, Affine::translate(request.x, request.y), 1.0, BlendMode::Normal);
     
     // Encode result
     let result_image = DynamicImage::ImageRgba8(result);
-    match encode_image(&result_image, &request.format) {
+    match encode_image(&result_image, &request.format, None) {
         Ok(base64) => CompositeResponse {
             success: true,
             image_base64: Some(base64),
@@ -135,32 +145,6 @@ pub fn composite_patch(request: CompositeRequest) -> CompositeResponse {
     }
 }
 
-/// Alpha blend two RGBA pixels
-fn alpha_blend(base: &image::Rgba<u8>, overlay: &image::Rgba<u8>) -> image::Rgba<u8> {
-    let alpha = overlay[3] as f32 / 255.0;
-    let inv_alpha = 1.0 - alpha;
-    
-    image::Rgba([
-        ((overlay[0] as f32 * alpha) + (base[0] as f32 * inv_alpha)) as u8,
-        ((overlay[1] as f32 * alpha) + (base[1] as f32 * inv_alpha)) as u8,
-        ((overlay[2] as f32 * alpha) + (base[2] as f32 * inv_alpha)) as u8,
-        255, // Fully opaque result
-    ])
-}
-
-/// Alpha blend with layer opacity
-fn alpha_blend_with_opacity(base: &image::Rgba<u8>, overlay: &image::Rgba<u8>, layer_opacity: f32) -> image::Rgba<u8> {
-    let alpha = (overlay[3] as f32 / 255.0) * layer_opacity;
-    let inv_alpha = 1.0 - alpha;
-    
-    image::Rgba([
-        ((overlay[0] as f32 * alpha) + (base[0] as f32 * inv_alpha)) as u8,
-        ((overlay[1] as f32 * alpha) + (base[1] as f32 * inv_alpha)) as u8,
-        ((overlay[2] as f32 * alpha) + (base[2] as f32 * inv_alpha)) as u8,
-        255, // Fully opaque result
-    ])
-}
-
 // === Layer Compositing ===
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -169,11 +153,34 @@ pub struct LayerData {
     pub image_data: String, // base64
     pub visible: bool,
     pub opacity: u8, // 0-100
-    pub x: Option<u32>,
-    pub y: Option<u32>,
+    /// Position in canvas pixels; may be negative or fractional
+    pub x: Option<f64>,
+    pub y: Option<f64>,
     pub width: Option<u32>,
     pub height: Option<u32>,
-    pub blend_mode: Option<String>,
+    /// Canvas blend mode name; normal when absent or not one the compositor knows
+    #[serde(default, deserialize_with = "blend_mode_or_normal")]
+    pub blend_mode: Option<BlendMode>,
+    /// Rotation, flip and skew about the centre of the layer's box
+    pub transform: Option<LayerTransform>,
+    /// Lasso outline in layer pixels, relative to the layer's top-left
+    pub polygon_points: Option<Vec<Point>>,
+    /// Width in pixels of the soft edge inside the outline
+    pub feather_radius: Option<f64>,
+    /// Outline of layers without polygon points; the whole box when absent
+    pub shape_type: Option<ShapeType>,
+}
+
+/// Read a blend mode name, falling back to normal so an unknown or legacy name
+/// (from an older project file, say) does not fail the whole export
+fn blend_mode_or_normal<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<BlendMode>, D::Error> {
+    let name = Option::<String>::deserialize(deserializer)?;
+    Ok(name.map(|name| {
+        BlendMode::deserialize(name.as_str().into_deserializer()).unwrap_or_else(|_: serde::de::value::Error| {
+            log::warn!("Unknown blend mode '{}', compositing as normal", name);
+            BlendMode::Normal
+        })
+    }))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -186,6 +193,9 @@ pub struct CompositeLayersRequest {
     pub canvas_height: u32,
     /// Output format
     pub format: String,
+    /// JPEG quality (1-100); the encoder default when absent. PNG and WebP are lossless
+    #[serde(default)]
+    pub quality: Option<u8>,
 }
 
 #[derive(Debug, Serialize)]
@@ -223,23 +233,24 @@ pub fn composite_layers(request: CompositeLayersRequest) -> CompositeLayersRespo
         };
         
         let layer_rgba = layer_img.to_rgba8();
+        let (source_w, source_h) = (layer_rgba.width() as f64, layer_rgba.height() as f64);
         let opacity = layer.opacity as f32 / 100.0;
+        let blend_mode = layer.blend_mode.unwrap_or_default();
         
         // Get position (default to 0,0 for base layers)
-        let pos_x = layer.x.unwrap_or(0);
-        let pos_y = layer.y.unwrap_or(0);
+        let pos_x = layer.x.unwrap_or(0.0);
+        let pos_y = layer.y.unwrap_or(0.0);
         
         // Resize if target dimensions are specified (for edit layers)
-        let final_rgba = if let (Some(target_w), Some(target_h)) = (layer.width, layer.height) {
+        let mut final_rgba = if let (Some(target_w), Some(target_h)) = (layer.width, layer.height) {
             if target_w > 0 && target_h > 0 && (target_w != layer_rgba.width() || target_h != layer_rgba.height()) {
                 // Resize to target dimensions
-                let resized = image::imageops::resize(
+                image::imageops::resize(
                     &layer_rgba,
                     target_w,
                     target_h,
                     image::imageops::FilterType::Lanczos3
-                );
-                resized
+                )
             } else {
                 layer_rgba
             }
@@ -247,23 +258,39 @@ pub fn composite_layers(request: CompositeLayersRequest) -> CompositeLayersRespo
             layer_rgba
         };
         
-        // Composite layer onto result
-        for (px, py, pixel) in final_rgba.enumerate_pixels() {
-            let target_x = pos_x + px;
-            let target_y = pos_y + py;
-            
-            // Check bounds
-            if target_x < result.width() && target_y < result.height() {
-                let base_pixel = result.get_pixel(target_x, target_y);
-                let blended = alpha_blend_with_opacity(base_pixel, pixel, opacity);
-                result.put_pixel(target_x, target_y, blended);
-            }
+        // Clip to the lasso or shape outline, scaled from the layer box to the resized image
+        let points = layer.polygon_points.as_deref().map(|points| {
+            let scale_x = final_rgba.width() as f64 / layer.width.map_or(final_rgba.width() as f64, f64::from);
+            let scale_y = final_rgba.height() as f64 / layer.height.map_or(final_rgba.height() as f64, f64::from);
+            points.iter().map(|p| Point { x: p.x * scale_x, y: p.y * scale_y }).collect::<Vec<_>>()
+        });
+        let shape = match &points {
+            Some(points) if points.len() >= 3 => MaskShape::Polygon(points),
+            _ => MaskShape::Shape(layer.shape_type.unwrap_or_default()),
+        };
+        let feather = layer.feather_radius.unwrap_or(0.0);
+        if let Some(mask) = layer_mask(final_rgba.width(), final_rgba.height(), shape, feather) {
+            apply_mask(&mut final_rgba, &mask);
         }
+
+        // Place the layer; transforms are defined on the source pixels, so undo the resize first
+        let placement = match layer.transform.filter(|t| *t != LayerTransform::default()) {
+            Some(transform) => {
+                let (w, h) = (final_rgba.width() as f64, final_rgba.height() as f64);
+                transform
+                    .placement((pos_x, pos_y, w, h), (source_w, source_h))
+                    .then(Affine::scale(source_w / w, source_h / h))
+            }
+            None => Affine::translate(pos_x, pos_y),
+        };
+
+        // Composite layer onto result
+        draw_layer(&mut result, &final_rgba, placement, opacity, blend_mode);
     }
     
     // Encode result
     let result_image = DynamicImage::ImageRgba8(result);
-    match encode_image(&result_image, &request.format) {
+    match encode_image(&result_image, &request.format, request.quality) {
         Ok(base64) => CompositeLayersResponse {
             success: true,
             image_base64: Some(base64),
@@ -277,3 +304,166 @@ pub fn composite_layers(request: CompositeLayersRequest) -> CompositeLayersRespo
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn png_base64(image: &RgbaImage) -> String {
+        encode_image(&DynamicImage::ImageRgba8(image.clone()), "png", None).unwrap()
+    }
+
+    fn layer(image: &RgbaImage, opacity: u8) -> LayerData {
+        LayerData {
+            id: "layer".to_string(),
+            image_data: png_base64(image),
+            visible: true,
+            opacity,
+            x: None,
+            y: None,
+            width: None,
+            height: None,
+            blend_mode: None,
+            transform: None,
+            polygon_points: None,
+            feather_radius: None,
+            shape_type: None,
+        }
+    }
+
+    #[test]
+    fn unknown_blend_modes_fall_back_to_normal() {
+        let parse = |blend_mode: serde_json::Value| {
+            let json = serde_json::json!({ "id": "layer", "image_data": "", "visible": true, "opacity": 100, "blend_mode": blend_mode });
+            serde_json::from_value::<LayerData>(json).unwrap().blend_mode
+        };
+        assert_eq!(parse(serde_json::json!("soft-light")), Some(BlendMode::SoftLight));
+        assert_eq!(parse(serde_json::json!("source-over")), Some(BlendMode::Normal));
+        assert_eq!(parse(serde_json::Value::Null), None);
+    }
+
+    #[test]
+    fn layers_can_sit_partly_off_the_top_left() {
+        let patch = RgbaImage::from_pixel(2, 2, Rgba([0, 255, 0, 255]));
+        let mut moved = layer(&patch, 100);
+        moved.x = Some(-1.0);
+        moved.y = Some(-1.0);
+
+        let response = composite_layers(CompositeLayersRequest {
+            layers: vec![moved],
+            canvas_width: 2,
+            canvas_height: 2,
+            format: "png".to_string(),
+            quality: None,
+        });
+        let output = decode_image(&response.image_base64.unwrap()).unwrap().to_rgba8();
+        assert_eq!(output.get_pixel(0, 0), &Rgba([0, 255, 0, 255]));
+        assert_eq!(output.get_pixel(1, 0)[3], 0);
+        assert_eq!(output.get_pixel(0, 1)[3], 0);
+    }
+
+    #[test]
+    fn rotation_is_applied_to_the_resized_layer() {
+        // A 1x2 column stretched to 2x4 and turned a quarter clockwise becomes a 4x2 row
+        let mut column = RgbaImage::new(1, 2);
+        column.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        column.put_pixel(0, 1, Rgba([0, 0, 255, 255]));
+        let mut turned = layer(&column, 100);
+        (turned.x, turned.y, turned.width, turned.height) = (Some(1.0), Some(-1.0), Some(2), Some(4));
+        turned.transform = Some(LayerTransform { rotation: 90.0, ..Default::default() });
+
+        let response = composite_layers(CompositeLayersRequest {
+            layers: vec![turned],
+            canvas_width: 4,
+            canvas_height: 2,
+            format: "png".to_string(),
+            quality: None,
+        });
+        let output = decode_image(&response.image_base64.unwrap()).unwrap().to_rgba8();
+        assert!(output.pixels().all(|p| p[3] == 255));
+        // The top of the column (red) ends up on the right
+        assert!(output.get_pixel(3, 0)[0] > 200 && output.get_pixel(3, 0)[2] < 50);
+        assert!(output.get_pixel(0, 0)[2] > 200 && output.get_pixel(0, 0)[0] < 50);
+    }
+
+    #[test]
+    fn lasso_layers_are_clipped_to_their_outline() {
+        let patch = RgbaImage::from_pixel(10, 10, Rgba([255, 255, 255, 255]));
+        let mut lasso = layer(&patch, 100);
+        // Drawn at 20x20, so the outline is in 20x20 layer pixels
+        (lasso.width, lasso.height) = (Some(20), Some(20));
+        lasso.polygon_points = Some(
+            [(0.0, 0.0), (20.0, 0.0), (0.0, 20.0)].map(|(x, y)| Point { x, y }).to_vec(),
+        );
+
+        let response = composite_layers(CompositeLayersRequest {
+            layers: vec![lasso],
+            canvas_width: 20,
+            canvas_height: 20,
+            format: "png".to_string(),
+            quality: None,
+        });
+        let output = decode_image(&response.image_base64.unwrap()).unwrap().to_rgba8();
+        assert_eq!(output.get_pixel(2, 2)[3], 255);
+        assert_eq!(output.get_pixel(17, 17)[3], 0);
+        // The diagonal edge is antialiased
+        assert!(output.get_pixel(10, 9)[3] > 0 && output.get_pixel(10, 9)[3] < 255);
+    }
+
+    #[test]
+    fn exported_layers_keep_transparency() {
+        // Left pixel transparent, right pixel half-transparent red
+        let mut base = RgbaImage::new(2, 1);
+        base.put_pixel(1, 0, Rgba([255, 0, 0, 128]));
+
+        for format in ["png", "webp"] {
+            let response = composite_layers(CompositeLayersRequest {
+                layers: vec![layer(&base, 100)],
+                canvas_width: 2,
+                canvas_height: 1,
+                format: format.to_string(),
+                quality: None,
+            });
+            let output = decode_image(&response.image_base64.unwrap()).unwrap().to_rgba8();
+            assert_eq!(output.get_pixel(0, 0)[3], 0, "{}", format);
+            assert_eq!(output.get_pixel(1, 0), &Rgba([255, 0, 0, 128]), "{}", format);
+        }
+
+        let response = composite_layers(CompositeLayersRequest {
+            layers: vec![layer(&base, 100)],
+            canvas_width: 2,
+            canvas_height: 1,
+            format: "jpg".to_string(),
+            quality: None,
+        });
+        assert!(response.success, "{:?}", response.error);
+    }
+
+    #[test]
+    fn jpeg_exports_use_the_requested_quality() {
+        let noise = RgbaImage::from_fn(32, 32, |x, y| {
+            Rgba([(x * 37 % 256) as u8, (y * 91 % 256) as u8, ((x ^ y) * 13 % 256) as u8, 255])
+        });
+        let size = |quality| {
+            let response = composite_layers(CompositeLayersRequest {
+                layers: vec![layer(&noise, 100)],
+                canvas_width: 32,
+                canvas_height: 32,
+                format: "jpeg".to_string(),
+                quality: Some(quality),
+            });
+            response.image_base64.unwrap().len()
+        };
+        assert!(size(20) < size(95));
+    }
+
+    #[test]
+    fn webp_exports_are_lossless_whatever_the_quality() {
+        let image = RgbaImage::from_fn(8, 8, |x, y| Rgba([(x * 31) as u8, (y * 29) as u8, 200, (x * y * 4) as u8]));
+        let encoded = encode_image(&DynamicImage::ImageRgba8(image.clone()), "webp", Some(20)).unwrap();
+
+        let decoded = image::load_from_memory(&STANDARD.decode(encoded).unwrap()).unwrap();
+        assert_eq!(decoded.to_rgba8(), image);
+    }
+}
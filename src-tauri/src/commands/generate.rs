@@ -1,12 +1,21 @@
 // BananaSlice - Generation Commands
 // Tauri commands for AI image generation
 
-use crate::api::NanoBananaClient;
-use crate::keystore;
+use crate::api::{
+    self, ApiError, AuthStyle, FillRequest, GenerateError, GeneratedImage, GenerationError, GenerationStage, ImageEditProvider,
+    InputTransform, KeyValidation, ModelFeedback, ProgressSink, ProviderConfig, RetryPolicy,
+};
+use super::network::http_client;
+use super::profiles::load_profiles;
+use crate::jobs::{self, JobInfo, JobOutcome, JobRegistry};
+use crate::keystore::{self, KeySource, KeyringError, ResolvedKey};
+use crate::profiles::{CredentialProfile, DEFAULT_PROFILE_ID};
 use base64::{engine::general_purpose::STANDARD, Engine};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GenerateRequest {
@@ -18,13 +27,134 @@ pub struct GenerateRequest {
     pub reference_images: Vec<String>, // Optional reference images as base64
     #[serde(default)]
     pub base_url: Option<String>, // Optional custom base URL
+    #[serde(default)]
+    pub auth: Option<AuthStyle>, // How the key is sent, defaults to the provider's convention
+    #[serde(default)]
+    pub provider: Option<String>, // Provider ID, defaults to the profile's provider or Gemini
+    #[serde(default)]
+    pub profile_id: Option<String>, // Credential profile, defaults to the default profile
+    #[serde(default)]
+    pub provider_options: serde_json::Value, // Provider-specific options
+    #[serde(default, alias = "num_variants")]
+    pub candidate_count: Option<u32>, // Number of variants to generate
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>, // Retry policy for transient failures
 }
 
+/// Upper bound on variants per generation
+const MAX_CANDIDATES: u32 = 4;
+
 #[derive(Debug, Serialize)]
 pub struct GenerateResponse {
     pub success: bool,
-    pub image_base64: Option<String>,
-    pub error: Option<String>,
+    pub image_base64: Option<String>, // First variant, for single-result callers
+    pub images: Vec<GeneratedImage>, // Every variant returned by the provider
+    pub cancelled: bool, // True when the job was cancelled (error kind is "cancelled")
+    pub error: Option<GenerateError>, // Typed error with a display message
+    pub key_source: Option<KeySource>, // Where the API key came from (never the key itself)
+    pub input_transform: Option<InputTransform>, // Downscaling/padding applied before upload, already undone
+    #[serde(flatten)]
+    pub feedback: ModelFeedback, // Model text, finish/block reasons and safety ratings
+}
+
+impl GenerateResponse {
+    fn failure(error: GenerateError) -> Self {
+        Self {
+            success: false,
+            image_base64: None,
+            images: Vec::new(),
+            cancelled: false,
+            error: Some(error),
+            key_source: None,
+            input_transform: None,
+            feedback: ModelFeedback::default(),
+        }
+    }
+
+    fn cancelled() -> Self {
+        Self {
+            success: false,
+            image_base64: None,
+            images: Vec::new(),
+            cancelled: true,
+            error: Some(GenerateError::new(GenerationError::Cancelled, "Generation cancelled")),
+            key_source: None,
+            input_transform: None,
+            feedback: ModelFeedback::default(),
+        }
+    }
+}
+
+/// Managed state holding in-flight generation jobs
+pub type GenerationJobs = JobRegistry<GenerateResponse>;
+
+/// Payload of every `generation://*` event
+#[derive(Debug, Clone, Serialize)]
+struct GenerationEvent {
+    job_id: String,
+    timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    candidate_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attempt: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delay_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Emits job lifecycle events to the frontend
+struct JobEvents {
+    app: AppHandle,
+    job_id: String,
+    candidate_count: Option<u32>,
+}
+
+impl JobEvents {
+    fn event(&self) -> GenerationEvent {
+        GenerationEvent {
+            job_id: self.job_id.clone(),
+            timestamp: jobs::now_ms(),
+            candidate_count: self.candidate_count,
+            image_count: None,
+            attempt: None,
+            delay_ms: None,
+            error: None,
+        }
+    }
+
+    fn emit(&self, name: &str, event: GenerationEvent) {
+        if let Err(e) = self.app.emit(&format!("generation://{}", name), event) {
+            log::warn!("Failed to emit generation://{}: {}", name, e);
+        }
+    }
+}
+
+impl ProgressSink for JobEvents {
+    fn stage(&self, stage: GenerationStage) {
+        let name = match stage {
+            GenerationStage::Uploading => "uploading",
+            GenerationStage::Waiting => "waiting",
+            GenerationStage::Decoding => "decoding",
+        };
+        self.emit(name, self.event());
+    }
+
+    fn retry(&self, attempt: u32, delay: Duration, reason: &str) {
+        let event = GenerationEvent {
+            attempt: Some(attempt),
+            delay_ms: Some(delay.as_millis() as u64),
+            error: Some(reason.to_string()),
+            ..self.event()
+        };
+        self.emit("retrying", event);
+    }
+}
+
+fn candidate_count(request: &GenerateRequest) -> u32 {
+    request.candidate_count.unwrap_or(1).clamp(1, MAX_CANDIDATES)
 }
 
 /// Get debug output directory
@@ -48,76 +178,366 @@ fn save_debug_image(base64_data: &str, filename: &str) {
     }
 }
 
-/// Generate fill for a selected region
-#[tauri::command]
-pub async fn generate_fill(request: GenerateRequest) -> GenerateResponse {
+/// Provider connection for a job: request fields first, then its credential profile
+pub(super) struct Connection {
+    pub provider: String,
+    pub api_key: Option<String>,
+    pub key_source: Option<KeySource>,
+    pub base_url: Option<String>,
+    pub auth: Option<AuthStyle>,
+    pub client: reqwest::Client, // Built from the network settings
+}
+
+impl Connection {
+    /// Build the connection's provider with the given options and retry policy
+    pub fn create_provider(
+        self,
+        options: serde_json::Value,
+        retry: RetryPolicy,
+    ) -> Result<Box<dyn ImageEditProvider>, ApiError> {
+        let config = ProviderConfig {
+            api_key: self.api_key,
+            base_url: self.base_url,
+            auth: self.auth,
+            options,
+            retry,
+            client: self.client,
+        };
+        api::create_provider(&self.provider, config)
+    }
+}
+
+/// Look up the key for a profile; only an explicitly named profile outranks the environment
+fn resolve_key(
+    profile: Option<&CredentialProfile>,
+    explicit: bool,
+    provider: &str,
+) -> Result<Option<ResolvedKey>, KeyringError> {
+    let profile_id = profile.map(|profile| profile.id.as_str());
+    if explicit {
+        keystore::resolve_api_key(profile_id, None, provider)
+    } else {
+        keystore::resolve_api_key(None, profile_id, provider)
+    }
+}
+
+/// Provider, secret and endpoint from a credential profile
+///
+/// The key comes from an explicitly requested profile, then the
+/// environment, then the keychain entry of the profile picked by default.
+pub(super) fn profile_connection(app: &AppHandle, profile_id: Option<&str>, provider: Option<&str>) -> Result<Connection, GenerateError> {
+    let (_, index) = load_profiles(app).map_err(|e| GenerateError::new(GenerationError::Unknown, e))?;
+    let profile = index.resolve(profile_id, provider).map_err(|e| {
+        GenerateError::new(GenerationError::BadRequest { field: Some("profile_id".to_string()) }, e.to_string())
+    })?;
+    if let Some(profile) = profile {
+        log::info!("Using credential profile '{}'", profile.id);
+    }
+
+    let provider = provider
+        .map(str::to_string)
+        .or_else(|| profile.map(|profile| profile.provider.clone()))
+        .unwrap_or_else(|| api::DEFAULT_PROVIDER.to_string());
+    let key = resolve_key(profile, profile_id.is_some(), &provider)
+        .map_err(|e| GenerateError::new(GenerationError::CredentialsLocked, e.to_string()))?;
+    if let Some(key) = &key {
+        log::info!("Using API key from {:?}", key.source);
+    }
+
+    let client = http_client(app).map_err(|e| {
+        GenerateError::new(GenerationError::BadRequest { field: Some("network".to_string()) }, e)
+    })?;
+    let (api_key, key_source) = key.map(|key| (key.secret, key.source)).unzip();
+    Ok(Connection {
+        provider,
+        api_key,
+        key_source,
+        base_url: profile.and_then(|profile| profile.base_url.clone()),
+        auth: profile.and_then(|profile| profile.auth.clone()),
+        client,
+    })
+}
+
+/// Resolve the provider and secret for a request, letting request fields override its profile
+fn resolve_connection(app: &AppHandle, request: &GenerateRequest) -> Result<Connection, GenerateError> {
+    let connection = profile_connection(app, request.profile_id.as_deref(), request.provider.as_deref())?;
+    Ok(Connection {
+        base_url: request.base_url.clone().filter(|url| !url.is_empty()).or(connection.base_url),
+        auth: request.auth.clone().or(connection.auth),
+        ..connection
+    })
+}
+
+/// Run a generation job to completion, emitting `done` or `failed` at the end
+async fn run_generation(
+    events: JobEvents,
+    request: GenerateRequest,
+    connection: Result<Connection, GenerateError>,
+) -> GenerateResponse {
+    let response = match connection {
+        Ok(connection) => generate(&request, connection, &events).await,
+        Err(error) => GenerateResponse::failure(error),
+    };
+    if response.success {
+        let event = GenerationEvent { image_count: Some(response.images.len()), ..events.event() };
+        events.emit("done", event);
+    } else {
+        let error = response.error.as_ref().map(|e| e.message.clone());
+        let event = GenerationEvent { error, ..events.event() };
+        events.emit("failed", event);
+    }
+    response
+}
+
+/// Generate fill for a selected region with the requested provider
+async fn generate(request: &GenerateRequest, connection: Connection, progress: &dyn ProgressSink) -> GenerateResponse {
     // Save input images for debugging
     log::info!("=== DEBUG: Saving input images ===");
     save_debug_image(&request.image_base64, "01_input_cropped.png");
     save_debug_image(&request.mask_base64, "02_input_mask.png");
     
-    // Get API key from secure storage
-    let api_key = match keystore::get_api_key() {
-        Ok(key) => key,
-        Err(_) => {
-            return GenerateResponse {
-                success: false,
-                image_base64: None,
-                error: Some("API key not configured. Please set your Gemini API key in Settings.".to_string()),
-            };
+    // The API key comes from the resolution chain (not every provider needs one)
+    let key_source = connection.key_source.clone();
+    let provider_id = connection.provider.clone();
+    let provider = match connection.create_provider(request.provider_options.clone(), request.retry.clone().unwrap_or_default()) {
+        Ok(provider) => provider,
+        Err(ApiError::ApiKeyMissing) => {
+            return GenerateResponse::failure(GenerateError::new(
+                GenerationError::InvalidApiKey,
+                keystore::missing_key_message(&provider_id),
+            ));
         }
+        Err(e) => return GenerateResponse::failure((&e).into()),
     };
-
-    // Resolve model name: known aliases map to Gemini models, otherwise use as-is
-    let model_name = match request.model.as_str() {
-        "nano-banana-pro" => "gemini-3-pro-image-preview",
-        "nano-banana-2" => "gemini-3.1-flash-image",
-        "nano-banana" => "gemini-2.5-flash-image",
-        custom => custom, // Allow custom model IDs
+    
+    // Fit the inputs to the provider's size and aspect-ratio limits
+    let prepared = match api::prepare_inputs(
+        &request.image_base64,
+        &request.mask_base64,
+        &request.reference_images,
+        &provider.input_limits(),
+    ) {
+        Ok(prepared) => prepared,
+        Err(e) => return GenerateResponse::failure((&e).into()),
     };
+    let input_transform = prepared.transform;
 
-    // Create client with optional custom base URL
-    let client = match &request.base_url {
-        Some(base_url) if !base_url.is_empty() => NanoBananaClient::with_base_url(api_key, base_url.clone()),
-        _ => NanoBananaClient::new(api_key),
-    };
-    
     // Convert reference images to &str slices
-    let ref_images: Vec<&str> = request.reference_images.iter().map(|s| s.as_str()).collect();
+    let ref_images: Vec<&str> = prepared.reference_images.iter().map(|s| s.as_str()).collect();
+
+    let fill_request = FillRequest {
+        model: &request.model,
+        prompt: &request.prompt,
+        image_base64: &prepared.image_base64,
+        mask_base64: &prepared.mask_base64,
+        reference_images: &ref_images,
+        candidate_count: candidate_count(request),
+        progress,
+    };
     
-    match client
-        .generate_fill(model_name, &request.prompt, &request.image_base64, &request.mask_base64, &ref_images)
-        .await
-    {
-        Ok(image_base64) => {
+    log::info!("Generating with provider: {}", provider.id());
+    match provider.inpaint(&fill_request).await {
+        Ok(mut images) => {
+            // A provider that returns nothing without an error still produced no image
+            let Some(first) = images.first() else {
+                let error = ApiError::NoImageGenerated(ModelFeedback::default());
+                return GenerateResponse { key_source, input_transform, ..GenerateResponse::failure((&error).into()) };
+            };
+
             // Save output image for debugging
             log::info!("=== DEBUG: Saving output image ===");
-            save_debug_image(&image_base64, "03_output_generated.png");
+            save_debug_image(&first.image_base64, "03_output_generated.png");
+
+            // Undo the preprocessing so each image lines up with the original crop
+            if let Some(transform) = &input_transform {
+                for image in &mut images {
+                    match api::restore_output(transform, &image.image_base64) {
+                        Ok(restored) => image.image_base64 = restored,
+                        Err(e) => return GenerateResponse { key_source, ..GenerateResponse::failure((&e).into()) },
+                    }
+                }
+            }
             
+            let (image_base64, feedback) = images.first().map(|image| (image.image_base64.clone(), image.feedback())).unzip();
             GenerateResponse {
                 success: true,
-                image_base64: Some(image_base64),
+                image_base64,
+                feedback: feedback.unwrap_or_default(),
+                images,
+                cancelled: false,
                 error: None,
+                key_source,
+                input_transform,
             }
         },
-        Err(e) => GenerateResponse {
-            success: false,
-            image_base64: None,
-            error: Some(e.to_string()),
-        },
+        Err(e) => {
+            log::error!("Generation failed: {}", e);
+            GenerateResponse {
+                feedback: e.feedback().cloned().unwrap_or_default(),
+                key_source,
+                input_transform,
+                ..GenerateResponse::failure((&e).into())
+            }
+        }
+    }
+}
+
+/// Spawn a generation job and register it
+fn spawn_job(app: &AppHandle, request: GenerateRequest) -> String {
+    let jobs = app.state::<GenerationJobs>();
+    let connection = resolve_connection(app, &request);
+    let provider = match &connection {
+        Ok(connection) => connection.provider.clone(),
+        Err(_) => request.provider.clone().unwrap_or_else(|| api::DEFAULT_PROVIDER.to_string()),
+    };
+    let info = JobInfo {
+        id: jobs.next_id(),
+        provider,
+        model: request.model.clone(),
+        started_at: jobs::now_ms(),
+    };
+    let events = JobEvents {
+        app: app.clone(),
+        job_id: info.id.clone(),
+        candidate_count: Some(candidate_count(&request)),
+    };
+    let id = info.id.clone();
+
+    log::info!("Starting generation job {}", id);
+    events.emit("queued", events.event());
+    jobs.spawn(info, run_generation(events, request, connection));
+    id
+}
+
+/// Wait for a job and turn its outcome into a response
+async fn collect_job(app: &AppHandle, job_id: &str) -> GenerateResponse {
+    match app.state::<GenerationJobs>().wait(job_id).await {
+        JobOutcome::Finished(response) => response,
+        JobOutcome::Cancelled => {
+            log::info!("Generation job {} was cancelled", job_id);
+            GenerateResponse::cancelled()
+        }
+        JobOutcome::Unknown => GenerateResponse::failure(GenerateError::new(
+            GenerationError::BadRequest { field: Some("job_id".to_string()) },
+            format!("Unknown generation job: {}", job_id),
+        )),
+    }
+}
+
+/// Generate fill for a selected region
+///
+/// Runs as a registered job, so it can still be cancelled through `cancel_generation`.
+#[tauri::command]
+pub async fn generate_fill(app: AppHandle, request: GenerateRequest) -> GenerateResponse {
+    let job_id = spawn_job(&app, request);
+    collect_job(&app, &job_id).await
+}
+
+/// Start a generation job and return its ID immediately
+#[tauri::command]
+pub async fn start_generation(app: AppHandle, request: GenerateRequest) -> String {
+    spawn_job(&app, request)
+}
+
+/// Wait for a started job to finish
+#[tauri::command]
+pub async fn await_generation(app: AppHandle, job_id: String) -> GenerateResponse {
+    collect_job(&app, &job_id).await
+}
+
+/// Cancel a running job, returning false if it was not found or already done
+#[tauri::command]
+pub fn cancel_generation(app: AppHandle, job_id: String) -> bool {
+    let cancelled = app.state::<GenerationJobs>().cancel(&job_id);
+    if cancelled {
+        log::info!("Cancelling generation job {}", job_id);
+        let events = JobEvents { app, job_id, candidate_count: None };
+        events.emit("cancelled", events.event());
     }
+    cancelled
 }
 
-/// Store the API key securely
+/// List generation jobs that are still running
 #[tauri::command]
-pub fn set_api_key(api_key: String) -> Result<(), String> {
-    keystore::store_api_key(&api_key).map_err(|e| e.to_string())
+pub fn list_generations(app: AppHandle) -> Vec<JobInfo> {
+    app.state::<GenerationJobs>().pending()
+}
+
+/// Probe a connection's provider without generating anything
+async fn probe_connection(connection: Connection, model: Option<&str>) -> KeyValidation {
+    match connection.create_provider(serde_json::Value::Null, RetryPolicy::default()) {
+        Ok(provider) => KeyValidation::from_probe(provider.probe(model).await, model),
+        Err(e) => KeyValidation::from_probe(Err(e), None),
+    }
 }
 
-/// Check if API key is configured
+/// Check a key with a cheap authenticated request to its provider
+///
+/// Uses `api_key` when given (e.g. a key typed into Settings but not saved
+/// yet), otherwise the key the profile would resolve to for a generation.
 #[tauri::command]
-pub fn has_api_key() -> bool {
-    keystore::has_api_key()
+pub async fn validate_api_key(
+    app: AppHandle,
+    profile_id: Option<String>,
+    model: Option<String>,
+    api_key: Option<String>,
+) -> Result<KeyValidation, String> {
+    let mut connection = profile_connection(&app, profile_id.as_deref(), None).map_err(|e| e.message)?;
+    if let Some(api_key) = api_key.map(|key| key.trim().to_string()).filter(|key| !key.is_empty()) {
+        connection.api_key = Some(api_key);
+        connection.key_source = None;
+    }
+    Ok(probe_connection(connection, model.as_deref()).await)
+}
+
+/// Store the API key securely, optionally validating it first
+///
+/// With `validate`, a key the provider does not accept is not stored and the
+/// validation result is returned instead.
+#[tauri::command]
+pub async fn set_api_key(app: AppHandle, api_key: String, validate: Option<bool>) -> Result<Option<KeyValidation>, String> {
+    let validation = if validate.unwrap_or(false) {
+        let (_, index) = load_profiles(&app)?;
+        let profile = index.get(DEFAULT_PROFILE_ID);
+        let connection = Connection {
+            provider: profile.map_or(api::DEFAULT_PROVIDER.to_string(), |profile| profile.provider.clone()),
+            api_key: Some(api_key.clone()),
+            key_source: None,
+            base_url: profile.and_then(|profile| profile.base_url.clone()),
+            auth: profile.and_then(|profile| profile.auth.clone()),
+            client: http_client(&app)?,
+        };
+        let validation = probe_connection(connection, None).await;
+        if !validation.key_accepted() {
+            log::warn!("Not storing API key: {:?}", validation);
+            return Ok(Some(validation));
+        }
+        Some(validation)
+    } else {
+        None
+    };
+
+    keystore::store_api_key(&api_key).map_err(|e| e.to_string())?;
+    Ok(validation)
+}
+
+/// Where the key for a profile (or the default connection) would come from
+#[tauri::command]
+pub fn get_api_key_source(app: AppHandle, profile_id: Option<String>) -> Result<Option<KeySource>, String> {
+    let (_, index) = load_profiles(&app)?;
+    let profile = index.resolve(profile_id.as_deref(), None).map_err(|e| e.to_string())?;
+    let provider = profile.map_or(api::DEFAULT_PROVIDER, |profile| profile.provider.as_str());
+    let key = resolve_key(profile, profile_id.is_some(), provider).map_err(|e| e.to_string())?;
+    Ok(key.map(|key| key.source))
+}
+
+/// Check if a key is available anywhere in the resolution chain
+#[tauri::command]
+pub fn has_api_key(app: AppHandle) -> bool {
+    match get_api_key_source(app, None) {
+        Ok(source) => source.is_some(),
+        Err(_) => keystore::has_api_key(),
+    }
 }
 
 /// Delete the stored API key
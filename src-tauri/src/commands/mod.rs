@@ -1,10 +1,23 @@
 // BananaSlice - Tauri Commands Module
 // Handles all IPC calls from the frontend
 
+mod aspect_ratio;
 mod composite;
 mod file;
 mod generate;
+mod models;
+mod network;
+mod profiles;
+mod secrets;
 
+pub use aspect_ratio::calculate_aspect_ratio_adjustment;
 pub use composite::{composite_patch, composite_layers};
 pub use file::{get_app_info, open_image, save_image};
-pub use generate::{generate_fill, set_api_key, has_api_key, delete_api_key};
+pub use generate::{
+    generate_fill, start_generation, await_generation, cancel_generation, list_generations,
+    set_api_key, validate_api_key, has_api_key, get_api_key_source, delete_api_key, GenerationJobs
+};
+pub use models::{list_models, ModelCache};
+pub use network::{get_network_settings, set_network_settings};
+pub use profiles::{list_profiles, add_profile, rename_profile, delete_profile, set_default_profile};
+pub use secrets::{get_secret_backend, use_encrypted_secret_file, unlock_secret_file, use_system_keychain};
@@ -0,0 +1,110 @@
+// BananaSlice - Model Discovery Commands
+// Lists the image models a profile can use, cached per key and endpoint
+
+use super::generate::{profile_connection, Connection};
+use crate::api::{ModelInfo, RetryPolicy};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+/// How long a model list is reused before the provider is asked again
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+struct CachedModels {
+    fetched_at: Instant,
+    models: Vec<ModelInfo>,
+}
+
+/// Managed state caching `list_models` results by key and endpoint
+#[derive(Default)]
+pub struct ModelCache(Mutex<HashMap<String, CachedModels>>);
+
+impl ModelCache {
+    fn get(&self, key: &str) -> Option<Vec<ModelInfo>> {
+        let cache = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache
+            .get(key)
+            .filter(|entry| entry.fetched_at.elapsed() < CACHE_TTL)
+            .map(|entry| entry.models.clone())
+    }
+
+    fn insert(&self, key: String, models: Vec<ModelInfo>) {
+        let mut cache = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache.insert(key, CachedModels { fetched_at: Instant::now(), models });
+    }
+}
+
+/// Cache key for a connection's model list
+///
+/// Keyed by the secret actually resolved rather than the requested profile, so
+/// changing the default profile or replacing its key is never served a stale list.
+/// Only a hash of the secret is kept.
+fn cache_key(connection: &Connection) -> String {
+    let mut hasher = DefaultHasher::new();
+    connection.api_key.hash(&mut hasher);
+    format!(
+        "{}|{}|{:x}",
+        connection.provider,
+        connection.base_url.as_deref().unwrap_or(""),
+        hasher.finish()
+    )
+}
+
+/// List the image-output models available to a profile
+///
+/// `base_url` overrides the profile's endpoint like `GenerateRequest.base_url`;
+/// `refresh` bypasses the cache.
+#[tauri::command]
+pub async fn list_models(
+    app: AppHandle,
+    profile_id: Option<String>,
+    base_url: Option<String>,
+    refresh: Option<bool>,
+) -> Result<Vec<ModelInfo>, String> {
+    let mut connection = profile_connection(&app, profile_id.as_deref(), None).map_err(|e| e.message)?;
+    if let Some(base_url) = base_url.filter(|url| !url.is_empty()) {
+        connection.base_url = Some(base_url);
+    }
+
+    let key = cache_key(&connection);
+    let cache = app.state::<ModelCache>();
+    if !refresh.unwrap_or(false) {
+        if let Some(models) = cache.get(&key) {
+            return Ok(models);
+        }
+    }
+
+    let provider = connection
+        .create_provider(serde_json::Value::Null, RetryPolicy::default())
+        .map_err(|e| e.to_string())?;
+    let models = provider.list_models().await.map_err(|e| e.to_string())?;
+    log::info!("Listed {} models for {}", models.len(), provider.id());
+    cache.insert(key, models.clone());
+    Ok(models)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection(api_key: Option<&str>) -> Connection {
+        Connection {
+            provider: "gemini".to_string(),
+            api_key: api_key.map(str::to_string),
+            key_source: None,
+            base_url: None,
+            auth: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    #[test]
+    fn a_new_key_gets_its_own_model_list() {
+        assert_eq!(cache_key(&connection(Some("old"))), cache_key(&connection(Some("old"))));
+        assert_ne!(cache_key(&connection(Some("old"))), cache_key(&connection(Some("new"))));
+        assert!(!cache_key(&connection(Some("old"))).contains("old"));
+    }
+}
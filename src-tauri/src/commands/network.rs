@@ -0,0 +1,79 @@
+// BananaSlice - Network Settings Commands
+// Persists timeouts, proxy and certificates in network.json and builds the shared HTTP client
+
+use crate::api::NetworkSettings;
+use crate::keystore::{self, PROXY_SECRET_ID};
+use reqwest::Client;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+/// Network settings as shown in Settings (the proxy password is never returned)
+#[derive(Debug, Serialize)]
+pub struct NetworkSettingsInfo {
+    #[serde(flatten)]
+    pub settings: NetworkSettings,
+    pub has_proxy_password: bool,
+}
+
+fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to resolve config directory: {}", e))?;
+    Ok(dir.join("network.json"))
+}
+
+/// Load the settings; a missing file means the defaults
+fn load_settings(app: &AppHandle) -> Result<NetworkSettings, String> {
+    match fs::read_to_string(settings_path(app)?) {
+        Ok(json) => serde_json::from_str(&json).map_err(|e| format!("Invalid network settings: {}", e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(NetworkSettings::default()),
+        Err(e) => Err(format!("Failed to read network settings: {}", e)),
+    }
+}
+
+/// Build the HTTP client every provider uses from the saved settings
+pub(crate) fn http_client(app: &AppHandle) -> Result<Client, String> {
+    let settings = load_settings(app)?;
+    let password = keystore::get_secret(PROXY_SECRET_ID).ok();
+    settings.build_client(password.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_network_settings(app: AppHandle) -> Result<NetworkSettingsInfo, String> {
+    Ok(NetworkSettingsInfo {
+        settings: load_settings(&app)?,
+        has_proxy_password: keystore::has_secret(PROXY_SECRET_ID),
+    })
+}
+
+/// Save network settings after checking they produce a working client
+///
+/// `proxy_password` replaces the stored password when given; an empty string removes it.
+#[tauri::command]
+pub fn set_network_settings(
+    app: AppHandle,
+    settings: NetworkSettings,
+    proxy_password: Option<String>,
+) -> Result<(), String> {
+    let password = match &proxy_password {
+        Some(password) => Some(password.clone()).filter(|p| !p.is_empty()),
+        None => keystore::get_secret(PROXY_SECRET_ID).ok(),
+    };
+    settings.build_client(password.as_deref()).map_err(|e| e.to_string())?;
+
+    let path = settings_path(&app)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("Failed to save network settings: {}", e))?;
+
+    match proxy_password.as_deref() {
+        Some("") => keystore::delete_secret(PROXY_SECRET_ID).map_err(|e| e.to_string()),
+        Some(password) => keystore::store_secret(PROXY_SECRET_ID, password).map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
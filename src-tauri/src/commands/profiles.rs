@@ -0,0 +1,118 @@
+// BananaSlice - Credential Profile Commands
+// Tauri commands for managing named provider connections
+
+use crate::api::AuthStyle;
+use crate::keystore;
+use crate::profiles::{CredentialProfile, ProfileIndex, ProfileStore, DEFAULT_PROFILE_ID};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+/// Profile as listed in Settings
+#[derive(Debug, Serialize)]
+pub struct ProfileInfo {
+    #[serde(flatten)]
+    pub profile: CredentialProfile,
+    pub is_default: bool,
+    pub has_secret: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddProfileRequest {
+    pub label: String,
+    pub provider: String,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub auth: Option<AuthStyle>,
+    #[serde(default)]
+    pub secret: Option<String>, // API key or service-account JSON; optional for local providers
+}
+
+fn profile_store(app: &AppHandle) -> Result<ProfileStore, String> {
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to resolve config directory: {}", e))?;
+    Ok(ProfileStore::new(dir.join("profiles.json")))
+}
+
+/// Load the profile index, adopting a key saved before profiles existed
+pub(crate) fn load_profiles(app: &AppHandle) -> Result<(ProfileStore, ProfileIndex), String> {
+    let store = profile_store(app)?;
+    let mut index = store.load().map_err(|e| e.to_string())?;
+
+    if index.get(DEFAULT_PROFILE_ID).is_none() && keystore::has_api_key() {
+        log::info!("Adopting existing API key as the default Gemini profile");
+        index.profiles.insert(
+            0,
+            CredentialProfile {
+                id: DEFAULT_PROFILE_ID.to_string(),
+                label: "Gemini".to_string(),
+                provider: "gemini".to_string(),
+                base_url: None,
+                auth: None,
+            },
+        );
+        index.default_profile.get_or_insert_with(|| DEFAULT_PROFILE_ID.to_string());
+        store.save(&index).map_err(|e| e.to_string())?;
+    }
+    Ok((store, index))
+}
+
+/// List every credential profile (secrets are never returned)
+#[tauri::command]
+pub fn list_profiles(app: AppHandle) -> Result<Vec<ProfileInfo>, String> {
+    let (_, index) = load_profiles(&app)?;
+    Ok(index
+        .profiles
+        .iter()
+        .map(|profile| ProfileInfo {
+            is_default: index.default_profile.as_deref() == Some(profile.id.as_str()),
+            has_secret: keystore::has_secret(&profile.id),
+            profile: profile.clone(),
+        })
+        .collect())
+}
+
+/// Add a profile and store its secret in the keychain
+#[tauri::command]
+pub fn add_profile(app: AppHandle, request: AddProfileRequest) -> Result<CredentialProfile, String> {
+    let (store, mut index) = load_profiles(&app)?;
+    let profile = index
+        .add(&request.label, &request.provider, request.base_url, request.auth)
+        .map_err(|e| e.to_string())?;
+
+    if let Some(secret) = request.secret.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        keystore::store_secret(&profile.id, secret).map_err(|e| e.to_string())?;
+    }
+    if let Err(e) = store.save(&index) {
+        let _ = keystore::delete_secret(&profile.id);
+        return Err(e.to_string());
+    }
+    Ok(profile)
+}
+
+/// Change a profile's display label
+#[tauri::command]
+pub fn rename_profile(app: AppHandle, profile_id: String, label: String) -> Result<(), String> {
+    let (store, mut index) = load_profiles(&app)?;
+    index.rename(&profile_id, &label).map_err(|e| e.to_string())?;
+    store.save(&index).map_err(|e| e.to_string())
+}
+
+/// Delete a profile and its keychain secret
+#[tauri::command]
+pub fn delete_profile(app: AppHandle, profile_id: String) -> Result<(), String> {
+    let (store, mut index) = load_profiles(&app)?;
+    index.remove(&profile_id).map_err(|e| e.to_string())?;
+    store.save(&index).map_err(|e| e.to_string())?;
+    keystore::delete_secret(&profile_id).map_err(|e| e.to_string())
+}
+
+/// Use a profile when a request does not name one
+#[tauri::command]
+pub fn set_default_profile(app: AppHandle, profile_id: String) -> Result<(), String> {
+    let (store, mut index) = load_profiles(&app)?;
+    index.set_default(&profile_id).map_err(|e| e.to_string())?;
+    store.save(&index).map_err(|e| e.to_string())
+}
@@ -0,0 +1,47 @@
+// BananaSlice - Secret Storage Commands
+// Choose between the system keychain and the encrypted credentials file
+
+use crate::keystore::{self, BackendStatus};
+use tauri::{AppHandle, Manager};
+
+/// Where secrets are currently stored, and whether the file backend still needs its passphrase
+#[tauri::command]
+pub fn get_secret_backend() -> BackendStatus {
+    keystore::backend_status()
+}
+
+/// Store secrets in an encrypted file instead of the keychain (for systems without a secret service)
+///
+/// Secrets already in the keychain are not moved; re-enter them afterwards.
+#[tauri::command]
+pub async fn use_encrypted_secret_file(app: AppHandle, passphrase: String) -> Result<BackendStatus, String> {
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to resolve config directory: {}", e))?;
+    run_key_derivation(move || keystore::use_encrypted_file(&dir, &passphrase)).await
+}
+
+/// Unlock the encrypted credentials file for this session
+#[tauri::command]
+pub async fn unlock_secret_file(passphrase: String) -> Result<BackendStatus, String> {
+    run_key_derivation(move || keystore::unlock_encrypted_file(&passphrase)).await
+}
+
+/// Run a passphrase operation off the async runtime; Argon2 takes a noticeable fraction of a second
+async fn run_key_derivation(
+    operation: impl FnOnce() -> Result<(), keystore::KeyringError> + Send + 'static,
+) -> Result<BackendStatus, String> {
+    tauri::async_runtime::spawn_blocking(operation)
+        .await
+        .map_err(|e| format!("Key derivation task failed: {}", e))?
+        .map_err(|e| e.to_string())?;
+    Ok(keystore::backend_status())
+}
+
+/// Return to the system keychain, deleting the encrypted credentials file
+#[tauri::command]
+pub fn use_system_keychain() -> Result<BackendStatus, String> {
+    keystore::use_keychain().map_err(|e| e.to_string())?;
+    Ok(keystore::backend_status())
+}
@@ -0,0 +1,201 @@
+// BananaSlice - Blend Modes
+// Separable and non-separable blend functions from the W3C Compositing and Blending spec
+
+use serde::{Deserialize, Serialize};
+
+/// How a layer's colour combines with the layers below it
+///
+/// Names match the canvas `globalCompositeOperation` values, so what the canvas shows
+/// and what gets exported use the same formulas.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    SoftLight,
+    HardLight,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl BlendMode {
+    /// The blended colour `B(Cb, Cs)` of a backdrop and source colour, channels in 0..=1
+    pub fn blend(self, backdrop: [f32; 3], source: [f32; 3]) -> [f32; 3] {
+        match self {
+            BlendMode::Hue => set_lum(set_sat(source, sat(backdrop)), lum(backdrop)),
+            BlendMode::Saturation => set_lum(set_sat(backdrop, sat(source)), lum(backdrop)),
+            BlendMode::Color => set_lum(source, lum(backdrop)),
+            BlendMode::Luminosity => set_lum(backdrop, lum(source)),
+            _ => [0, 1, 2].map(|i| self.blend_channel(backdrop[i], source[i])),
+        }
+    }
+
+    /// Separable modes, one channel at a time
+    fn blend_channel(self, cb: f32, cs: f32) -> f32 {
+        match self {
+            BlendMode::Multiply => cb * cs,
+            BlendMode::Screen => screen(cb, cs),
+            BlendMode::Overlay => hard_light(cs, cb),
+            BlendMode::SoftLight => soft_light(cb, cs),
+            BlendMode::HardLight => hard_light(cb, cs),
+            BlendMode::Darken => cb.min(cs),
+            BlendMode::Lighten => cb.max(cs),
+            BlendMode::ColorDodge => {
+                if cb == 0.0 {
+                    0.0
+                } else if cs >= 1.0 {
+                    1.0
+                } else {
+                    (cb / (1.0 - cs)).min(1.0)
+                }
+            }
+            BlendMode::ColorBurn => {
+                if cb >= 1.0 {
+                    1.0
+                } else if cs == 0.0 {
+                    0.0
+                } else {
+                    1.0 - ((1.0 - cb) / cs).min(1.0)
+                }
+            }
+            BlendMode::Difference => (cb - cs).abs(),
+            BlendMode::Exclusion => cb + cs - 2.0 * cb * cs,
+            // Normal, and the non-separable modes which never get here
+            _ => cs,
+        }
+    }
+}
+
+fn screen(cb: f32, cs: f32) -> f32 {
+    cb + cs - cb * cs
+}
+
+fn hard_light(cb: f32, cs: f32) -> f32 {
+    if cs <= 0.5 {
+        cb * 2.0 * cs
+    } else {
+        screen(cb, 2.0 * cs - 1.0)
+    }
+}
+
+fn soft_light(cb: f32, cs: f32) -> f32 {
+    if cs <= 0.5 {
+        cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb)
+    } else {
+        let d = if cb <= 0.25 { ((16.0 * cb - 12.0) * cb + 4.0) * cb } else { cb.sqrt() };
+        cb + (2.0 * cs - 1.0) * (d - cb)
+    }
+}
+
+fn lum(c: [f32; 3]) -> f32 {
+    0.3 * c[0] + 0.59 * c[1] + 0.11 * c[2]
+}
+
+/// Pull a colour back into gamut while keeping its luminosity
+fn clip_color(c: [f32; 3]) -> [f32; 3] {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+    let mut c = c;
+    if n < 0.0 {
+        c = c.map(|v| l + (v - l) * l / (l - n));
+    }
+    if x > 1.0 {
+        c = c.map(|v| l + (v - l) * (1.0 - l) / (x - l));
+    }
+    c
+}
+
+fn set_lum(c: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - lum(c);
+    clip_color(c.map(|v| v + d))
+}
+
+fn sat(c: [f32; 3]) -> f32 {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+
+fn set_sat(c: [f32; 3], s: f32) -> [f32; 3] {
+    let mut order = [0, 1, 2];
+    order.sort_by(|&a, &b| c[a].total_cmp(&c[b]));
+    let [min, mid, max] = order;
+
+    let mut result = [0.0; 3];
+    if c[max] > c[min] {
+        result[mid] = (c[mid] - c[min]) * s / (c[max] - c[min]);
+        result[max] = s;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for i in 0..3 {
+            assert!((actual[i] - expected[i]).abs() < 1e-4, "got {:?}, expected {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn separable_modes_match_reference_values() {
+        let backdrop = [0.2, 0.5, 0.8];
+        let source = [0.6, 0.5, 0.1];
+        let cases = [
+            (BlendMode::Normal, [0.6, 0.5, 0.1]),
+            (BlendMode::Multiply, [0.12, 0.25, 0.08]),
+            (BlendMode::Screen, [0.68, 0.75, 0.82]),
+            (BlendMode::Overlay, [0.24, 0.5, 0.64]),
+            (BlendMode::SoftLight, [0.2496, 0.5, 0.672]),
+            (BlendMode::HardLight, [0.36, 0.5, 0.16]),
+            (BlendMode::Darken, [0.2, 0.5, 0.1]),
+            (BlendMode::Lighten, [0.6, 0.5, 0.8]),
+            (BlendMode::ColorDodge, [0.5, 1.0, 0.8888889]),
+            (BlendMode::ColorBurn, [0.0, 0.0, 0.0]),
+            (BlendMode::Difference, [0.4, 0.0, 0.7]),
+            (BlendMode::Exclusion, [0.56, 0.5, 0.74]),
+        ];
+        for (mode, expected) in cases {
+            assert_close(mode.blend(backdrop, source), expected);
+        }
+    }
+
+    #[test]
+    fn non_separable_modes_swap_hue_saturation_and_luminosity() {
+        let red = [1.0, 0.0, 0.0];
+        let grey = [0.5, 0.5, 0.5];
+
+        // Grey has no hue or saturation to give, so the result is a grey at the backdrop's luminosity
+        assert_close(BlendMode::Hue.blend(red, grey), [0.3, 0.3, 0.3]);
+        assert_close(BlendMode::Saturation.blend(red, grey), [0.3, 0.3, 0.3]);
+        // Red over grey keeps the grey's luminosity
+        assert_close(BlendMode::Color.blend(grey, red), [1.0, 0.2857143, 0.2857143]);
+        assert!((lum(BlendMode::Color.blend(grey, red)) - 0.5).abs() < 1e-4);
+        assert_close(BlendMode::Luminosity.blend(red, grey), [1.0, 0.2857143, 0.2857143]);
+    }
+
+    #[test]
+    fn blend_modes_round_trip_through_canvas_names() {
+        let names = [
+            "normal", "multiply", "screen", "overlay", "soft-light", "hard-light", "darken", "lighten",
+            "color-dodge", "color-burn", "difference", "exclusion", "hue", "saturation", "color", "luminosity",
+        ];
+        for name in names {
+            let mode: BlendMode = serde_json::from_str(&format!("\"{}\"", name)).unwrap();
+            assert_eq!(serde_json::to_string(&mode).unwrap(), format!("\"{}\"", name));
+        }
+        assert_eq!(serde_json::to_string(&BlendMode::ColorDodge).unwrap(), "\"color-dodge\"");
+    }
+}
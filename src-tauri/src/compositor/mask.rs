@@ -0,0 +1,216 @@
+// BananaSlice - Layer Masks
+// Antialiased polygon, ellipse and rectangle coverage with a distance-based feather
+
+use image::{GrayImage, Luma, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// A point in layer pixels, relative to the layer's top-left
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// The outline of a shape layer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShapeType {
+    #[default]
+    Rect,
+    Ellipse,
+}
+
+/// The outline a layer is clipped to
+#[derive(Debug, Clone, Copy)]
+pub enum MaskShape<'a> {
+    Shape(ShapeType),
+    Polygon(&'a [Point]),
+}
+
+/// Coverage of a pixel whose centre is `distance` inside the outline (negative outside)
+///
+/// Without feathering this is a one-pixel antialiasing ramp across the edge; feathering
+/// widens the ramp inwards by `feather` pixels.
+fn coverage(distance: f64, feather: f64) -> u8 {
+    let alpha = ((distance + 0.5) / (feather + 1.0)).clamp(0.0, 1.0);
+    (alpha * 255.0).round() as u8
+}
+
+/// Rasterize a layer's mask at `width`×`height`
+///
+/// Returns `None` when there is nothing to mask: an unfeathered rectangle leaves every
+/// pixel opaque, and an unfeathered ellipse layer already carries its outline in its
+/// pixels. Shape feathering follows the shape's own outline, the same ramp the canvas
+/// preview draws.
+pub fn layer_mask(width: u32, height: u32, shape: MaskShape, feather: f64) -> Option<GrayImage> {
+    let feather = feather.max(0.0);
+    match shape {
+        MaskShape::Polygon(points) if points.len() >= 3 => Some(polygon_mask(width, height, points, feather)),
+        MaskShape::Polygon(_) | MaskShape::Shape(ShapeType::Rect) => {
+            if feather == 0.0 {
+                return None;
+            }
+            let (w, h) = (width as f64, height as f64);
+            Some(GrayImage::from_fn(width, height, |x, y| {
+                let (cx, cy) = (x as f64 + 0.5, y as f64 + 0.5);
+                Luma([coverage(cx.min(w - cx).min(cy).min(h - cy), feather)])
+            }))
+        }
+        MaskShape::Shape(ShapeType::Ellipse) => (feather > 0.0).then(|| ellipse_mask(width, height, feather)),
+    }
+}
+
+/// Multiply an image's alpha by a mask of the same size
+pub fn apply_mask(image: &mut RgbaImage, mask: &GrayImage) {
+    for (pixel, coverage) in image.pixels_mut().zip(mask.pixels()) {
+        pixel[3] = ((pixel[3] as u32 * coverage[0] as u32 + 127) / 255) as u8;
+    }
+}
+
+/// Inscribed ellipse, using the first-order distance estimate `g / |∇g|` for
+/// `g = sqrt((x/a)² + (y/b)²) - 1`, which is exact for circles
+fn ellipse_mask(width: u32, height: u32, feather: f64) -> GrayImage {
+    let (a, b) = (width as f64 / 2.0, height as f64 / 2.0);
+    GrayImage::from_fn(width, height, |x, y| {
+        let (dx, dy) = (x as f64 + 0.5 - a, y as f64 + 0.5 - b);
+        let s = ((dx / a).powi(2) + (dy / b).powi(2)).sqrt();
+        let gradient = ((dx / (a * a)).powi(2) + (dy / (b * b)).powi(2)).sqrt();
+        let distance = if gradient < 1e-12 { a.min(b) } else { (1.0 - s) * s / gradient };
+        Luma([coverage(distance, feather)])
+    })
+}
+
+fn segment_distance(px: f64, py: f64, a: Point, b: Point) -> f64 {
+    let (ex, ey) = (b.x - a.x, b.y - a.y);
+    let length_sq = ex * ex + ey * ey;
+    let t = if length_sq == 0.0 { 0.0 } else { (((px - a.x) * ex + (py - a.y) * ey) / length_sq).clamp(0.0, 1.0) };
+    (px - a.x - t * ex).hypot(py - a.y - t * ey)
+}
+
+/// Even-odd fill of a closed polygon, with coverage from the distance to its outline
+fn polygon_mask(width: u32, height: u32, points: &[Point], feather: f64) -> GrayImage {
+    let (w, h) = (width as usize, height as usize);
+    let edges: Vec<(Point, Point)> = points.iter().copied().zip(points.iter().copied().cycle().skip(1)).collect();
+
+    // Distance from each pixel centre to the outline; only pixels within `band`
+    // of an edge need it, everything further is fully in or out
+    let band = feather + 1.0;
+    let mut distance = vec![band; w * h];
+    for &(a, b) in &edges {
+        let x0 = ((a.x.min(b.x) - band).floor().max(0.0) as usize).min(w);
+        let x1 = ((a.x.max(b.x) + band).ceil().max(0.0) as usize).min(w);
+        let y0 = ((a.y.min(b.y) - band).floor().max(0.0) as usize).min(h);
+        let y1 = ((a.y.max(b.y) + band).ceil().max(0.0) as usize).min(h);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let d = segment_distance(x as f64 + 0.5, y as f64 + 0.5, a, b);
+                let slot = &mut distance[y * w + x];
+                if d < *slot {
+                    *slot = d;
+                }
+            }
+        }
+    }
+
+    let mut mask = GrayImage::new(width, height);
+    let mut crossings = Vec::new();
+    for y in 0..h {
+        let cy = y as f64 + 0.5;
+        crossings.clear();
+        for &(a, b) in &edges {
+            if (a.y <= cy) != (b.y <= cy) {
+                crossings.push(a.x + (cy - a.y) * (b.x - a.x) / (b.y - a.y));
+            }
+        }
+        crossings.sort_by(f64::total_cmp);
+
+        let mut inside = vec![false; w];
+        for span in crossings.chunks_exact(2) {
+            // Pixels whose centres fall inside the span
+            let start = ((span[0] - 0.5).ceil().max(0.0) as usize).min(w);
+            let end = ((span[1] - 0.5).ceil().max(0.0) as usize).min(w);
+            inside[start..end].fill(true);
+        }
+        for x in 0..w {
+            let d = distance[y * w + x];
+            let signed = if inside[x] { d } else { -d };
+            mask.put_pixel(x as u32, y as u32, Luma([coverage(signed, feather)]));
+        }
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(from: f64, to: f64) -> Vec<Point> {
+        [(from, from), (to, from), (to, to), (from, to)].map(|(x, y)| Point { x, y }).to_vec()
+    }
+
+    #[test]
+    fn polygon_edges_are_antialiased() {
+        let points = square(2.0, 8.0);
+        let mask = layer_mask(10, 10, MaskShape::Polygon(&points), 0.0).unwrap();
+        assert_eq!(mask.get_pixel(5, 5)[0], 255);
+        assert_eq!(mask.get_pixel(2, 5)[0], 255);
+        assert_eq!(mask.get_pixel(1, 5)[0], 0);
+        assert_eq!(mask.get_pixel(0, 0)[0], 0);
+
+        // An edge through the middle of a pixel covers half of it
+        let points = square(2.5, 7.5);
+        let mask = layer_mask(10, 10, MaskShape::Polygon(&points), 0.0).unwrap();
+        assert_eq!(mask.get_pixel(2, 5)[0], 128);
+        assert_eq!(mask.get_pixel(3, 5)[0], 255);
+        assert_eq!(mask.get_pixel(7, 5)[0], 128);
+    }
+
+    #[test]
+    fn feather_ramps_inwards_from_the_outline() {
+        let points = square(0.0, 20.0);
+        let mask = layer_mask(20, 20, MaskShape::Polygon(&points), 4.0).unwrap();
+        let row: Vec<u8> = (0..10).map(|x| mask.get_pixel(x, 10)[0]).collect();
+        assert!(row.windows(2).all(|pair| pair[0] < pair[1] || pair[1] == 255), "{:?}", row);
+        assert_eq!(row[0], 51);
+        assert_eq!(row[4], 255);
+
+        // The same ramp for a rectangle layer without points
+        let rect = layer_mask(20, 20, MaskShape::Shape(ShapeType::Rect), 4.0).unwrap();
+        assert_eq!(rect.get_pixel(0, 10), mask.get_pixel(0, 10));
+        assert!(layer_mask(20, 20, MaskShape::Shape(ShapeType::Rect), 0.0).is_none());
+        assert!(layer_mask(20, 20, MaskShape::Polygon(&points[..2]), 0.0).is_none());
+    }
+
+    #[test]
+    fn ellipse_is_inscribed_in_the_layer() {
+        let mask = ellipse_mask(20, 10, 0.0);
+        assert_eq!(mask.get_pixel(10, 5)[0], 255);
+        assert_eq!(mask.get_pixel(0, 0)[0], 0);
+        assert_eq!(mask.get_pixel(19, 9)[0], 0);
+        // Along the axes the edge sits on the layer bounds
+        assert_eq!(mask.get_pixel(1, 5)[0], 255);
+        assert!(mask.pixels().any(|p| p[0] > 0 && p[0] < 255));
+    }
+
+    #[test]
+    fn ellipse_feather_matches_the_canvas_preview() {
+        // The shape already has its outline, so only a feather adds a mask
+        assert!(layer_mask(20, 10, MaskShape::Shape(ShapeType::Ellipse), 0.0).is_none());
+
+        // Same values as ellipseFeatherAlpha in layerCompositor.ts
+        let mask = layer_mask(20, 10, MaskShape::Shape(ShapeType::Ellipse), 2.0).unwrap();
+        let expected = [((0, 5), 80), ((1, 5), 163), ((2, 5), 244), ((3, 5), 255), ((10, 0), 84), ((10, 1), 169), ((3, 2), 148)];
+        for ((x, y), value) in expected {
+            assert_eq!(mask.get_pixel(x, y)[0], value, "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn masks_multiply_alpha() {
+        let mut image = RgbaImage::from_pixel(2, 1, image::Rgba([9, 9, 9, 200]));
+        let mask = GrayImage::from_raw(2, 1, vec![255, 128]).unwrap();
+        apply_mask(&mut image, &mask);
+        assert_eq!(image.get_pixel(0, 0)[3], 200);
+        assert_eq!(image.get_pixel(1, 0)[3], 100);
+    }
+}
@@ -0,0 +1,261 @@
+// BananaSlice - Layer Compositor
+// Pixel-level compositing shared by the export and patch commands
+
+mod blend;
+mod mask;
+mod resample;
+mod transform;
+
+pub use blend::BlendMode;
+pub use mask::{apply_mask, layer_mask, MaskShape, Point, ShapeType};
+pub use transform::{Affine, LayerTransform};
+
+use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+/// A 0..=1 channel value back to 8 bits
+fn to_u8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Composite `source` over `backdrop` with a blend mode and extra layer opacity
+///
+/// Follows the canvas `source-over` with `globalAlpha` and `globalCompositeOperation`,
+/// so exports match the preview: blending and the Porter-Duff "over" both work on the
+/// sRGB-encoded colour, premultiplied by alpha, and the result keeps real alpha
+/// wherever both pixels are see-through.
+pub fn composite_pixel(backdrop: &Rgba<u8>, source: &Rgba<u8>, opacity: f32, mode: BlendMode) -> Rgba<u8> {
+    let alpha_s = (source[3] as f32 / 255.0) * opacity.clamp(0.0, 1.0);
+    if alpha_s <= 0.0 {
+        return *backdrop;
+    }
+    let alpha_b = backdrop[3] as f32 / 255.0;
+    let alpha_o = alpha_s + alpha_b * (1.0 - alpha_s);
+
+    let cb = [0, 1, 2].map(|i| backdrop[i] as f32 / 255.0);
+    let cs = [0, 1, 2].map(|i| source[i] as f32 / 255.0);
+    // Where the backdrop is opaque the source shows blended, elsewhere as itself
+    let source_colour = if mode == BlendMode::Normal || alpha_b == 0.0 {
+        cs
+    } else {
+        let blended = mode.blend(cb, cs);
+        [0, 1, 2].map(|i| (1.0 - alpha_b) * cs[i] + alpha_b * blended[i])
+    };
+
+    let channel = |i: usize| to_u8((source_colour[i] * alpha_s + cb[i] * alpha_b * (1.0 - alpha_s)) / alpha_o);
+    Rgba([channel(0), channel(1), channel(2), to_u8(alpha_o)])
+}
+
+/// Draw `layer` onto `canvas`, mapping its pixels through `placement`
+///
+/// The layer is clipped on every side. Pure translations by whole pixels copy pixels
+/// directly; anything else is inverse-mapped and resampled bilinearly, which also
+/// antialiases the layer's edges.
+pub fn draw_layer(canvas: &mut RgbaImage, layer: &RgbaImage, placement: Affine, opacity: f32, mode: BlendMode) {
+    let Some(inverse) = placement.invert() else {
+        return;
+    };
+    let integral = placement
+        .as_translation()
+        .filter(|(x, y)| x.fract() == 0.0 && y.fract() == 0.0)
+        .map(|(x, y)| (x as i64, y as i64));
+
+    // Bounding box of the placed layer, with a pixel to spare for the resampled edge
+    let (width, height) = (layer.width() as f64, layer.height() as f64);
+    let corners = [(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)].map(|(x, y)| placement.apply(x, y));
+    let margin = if integral.is_some() { 0.0 } else { 1.0 };
+    let min_x = corners.iter().map(|c| c.0).fold(f64::MAX, f64::min) - margin;
+    let min_y = corners.iter().map(|c| c.1).fold(f64::MAX, f64::min) - margin;
+    let max_x = corners.iter().map(|c| c.0).fold(f64::MIN, f64::max) + margin;
+    let max_y = corners.iter().map(|c| c.1).fold(f64::MIN, f64::max) + margin;
+    let (left, top) = ((min_x.floor() as i64).max(0), (min_y.floor() as i64).max(0));
+    let right = (max_x.ceil() as i64).min(canvas.width() as i64);
+    let bottom = (max_y.ceil() as i64).min(canvas.height() as i64);
+
+    for ty in top..bottom {
+        for tx in left..right {
+            let source = match integral {
+                Some((x, y)) => *layer.get_pixel((tx - x) as u32, (ty - y) as u32),
+                None => {
+                    // Sample at the pixel centre; sample positions are pixel indices
+                    let (u, v) = inverse.apply(tx as f64 + 0.5, ty as f64 + 0.5);
+                    resample::to_rgba(resample::sample_bilinear(layer, u - 0.5, v - 0.5))
+                }
+            };
+            if source[3] == 0 {
+                continue;
+            }
+            let backdrop = canvas.get_pixel(tx as u32, ty as u32);
+            let blended = composite_pixel(backdrop, &source, opacity, mode);
+            canvas.put_pixel(tx as u32, ty as u32, blended);
+        }
+    }
+}
+
+/// Flatten an image onto a solid background, for formats without alpha
+pub fn flatten(image: &RgbaImage, background: Rgb<u8>) -> RgbImage {
+    let backdrop = Rgba([background[0], background[1], background[2], 255]);
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let pixel = composite_pixel(&backdrop, image.get_pixel(x, y), 1.0, BlendMode::Normal);
+        Rgb([pixel[0], pixel[1], pixel[2]])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLEAR: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+    fn over(backdrop: [u8; 4], source: [u8; 4]) -> [u8; 4] {
+        composite_pixel(&Rgba(backdrop), &Rgba(source), 1.0, BlendMode::Normal).0
+    }
+
+    #[test]
+    fn over_keeps_transparency() {
+        assert_eq!(composite_pixel(&CLEAR, &CLEAR, 1.0, BlendMode::Normal), CLEAR);
+        assert_eq!(composite_pixel(&CLEAR, &Rgba([10, 20, 30, 255]), 0.0, BlendMode::Multiply), CLEAR);
+        // Half-transparent red over nothing stays half-transparent red, not darkened red
+        assert_eq!(over([0, 0, 0, 0], [255, 0, 0, 128]), [255, 0, 0, 128]);
+        assert_eq!(over([0, 0, 0, 0], [255, 0, 0, 255]), [255, 0, 0, 255]);
+        // Opacity scales the source alpha
+        assert_eq!(
+            composite_pixel(&CLEAR, &Rgba([0, 255, 0, 255]), 0.5, BlendMode::Normal),
+            Rgba([0, 255, 0, 128])
+        );
+    }
+
+    /// The canvas `source-over` with `globalAlpha`, as the preview draws it: premultiplied
+    /// `co = cs·αs + cb·αb·(1 - αs)` on the sRGB-encoded 8-bit values
+    fn canvas_source_over(backdrop: [u8; 4], source: [u8; 4], global_alpha: f32) -> [u8; 4] {
+        let alpha_s = source[3] as f64 / 255.0 * global_alpha as f64;
+        let alpha_b = backdrop[3] as f64 / 255.0;
+        let alpha_o = alpha_s + alpha_b * (1.0 - alpha_s);
+        if alpha_o == 0.0 {
+            return [0; 4];
+        }
+        let channel = |i: usize| {
+            let premultiplied = source[i] as f64 * alpha_s + backdrop[i] as f64 * alpha_b * (1.0 - alpha_s);
+            (premultiplied / alpha_o).round() as u8
+        };
+        [channel(0), channel(1), channel(2), (alpha_o * 255.0).round() as u8]
+    }
+
+    #[test]
+    fn over_matches_the_canvas_preview() {
+        // 50% white over black shows as mid grey on the canvas, and exports the same
+        assert_eq!(over([0, 0, 0, 255], [255, 255, 255, 128]), [128, 128, 128, 255]);
+        // 50% red over 50% blue: alpha 0.5 + 0.5 × 0.5, colour weighted by coverage
+        assert_eq!(over([0, 0, 255, 128], [255, 0, 0, 128]), [170, 0, 85, 192]);
+
+        let values = [0, 1, 64, 127, 128, 200, 254, 255];
+        for backdrop in values.map(|v| [v, 255 - v, v / 2, 255 - v / 3]) {
+            for source in values.map(|v| [255 - v, v / 3, v, v]) {
+                for opacity in [0.25, 0.5, 1.0] {
+                    let exported = composite_pixel(&Rgba(backdrop), &Rgba(source), opacity, BlendMode::Normal).0;
+                    let preview = canvas_source_over(backdrop, source, opacity);
+                    for i in 0..4 {
+                        assert!(
+                            exported[i].abs_diff(preview[i]) <= 1,
+                            "{:?} over {:?} at {}: export {:?}, preview {:?}",
+                            source,
+                            backdrop,
+                            opacity,
+                            exported,
+                            preview
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn blend_modes_apply_only_over_opaque_backdrop() {
+        let grey = Rgba([128, 128, 128, 255]);
+        // Multiply by white is a no-op; over nothing it is plain normal
+        assert_eq!(composite_pixel(&grey, &Rgba([255, 255, 255, 255]), 1.0, BlendMode::Multiply), grey);
+        assert_eq!(
+            composite_pixel(&CLEAR, &Rgba([255, 255, 255, 255]), 1.0, BlendMode::Multiply),
+            Rgba([255, 255, 255, 255])
+        );
+        assert_eq!(
+            composite_pixel(&grey, &Rgba([128, 128, 128, 255]), 1.0, BlendMode::Difference),
+            Rgba([0, 0, 0, 255])
+        );
+    }
+
+    #[test]
+    fn layers_clip_on_every_side() {
+        let layer = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 255, 255]));
+        let mut canvas = RgbaImage::new(3, 3);
+        draw_layer(&mut canvas, &layer, Affine::translate(-1.0, -1.0), 1.0, BlendMode::Normal);
+        draw_layer(&mut canvas, &layer, Affine::translate(2.0, 2.0), 1.0, BlendMode::Normal);
+
+        let covered: Vec<bool> = canvas.pixels().map(|p| p[3] == 255).collect();
+        assert_eq!(covered, [true, false, false, false, false, false, false, false, true]);
+
+        // Entirely off-canvas draws nothing
+        draw_layer(&mut canvas, &layer, Affine::translate(-5.0, 10.0), 1.0, BlendMode::Normal);
+        assert_eq!(canvas.pixels().filter(|p| p[3] > 0).count(), 2);
+    }
+
+    #[test]
+    fn fractional_offsets_split_coverage() {
+        let layer = RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 255]));
+        let mut canvas = RgbaImage::new(3, 1);
+        draw_layer(&mut canvas, &layer, Affine::translate(0.5, 0.0), 1.0, BlendMode::Normal);
+        assert_eq!(canvas.get_pixel(0, 0), &Rgba([255, 255, 255, 128]));
+        assert_eq!(canvas.get_pixel(1, 0), &Rgba([255, 255, 255, 128]));
+        assert_eq!(canvas.get_pixel(2, 0), &Rgba([0, 0, 0, 0]));
+
+        // Half a pixel off the left edge leaves half of the first column
+        let mut canvas = RgbaImage::new(2, 1);
+        draw_layer(&mut canvas, &layer, Affine::translate(-0.5, 0.0), 1.0, BlendMode::Normal);
+        assert_eq!(canvas.get_pixel(0, 0), &Rgba([255, 255, 255, 128]));
+        assert_eq!(canvas.get_pixel(1, 0)[3], 0);
+    }
+
+    #[test]
+    fn rotated_and_flipped_layers_resample_exactly_on_pixel_centres() {
+        let mut strip = RgbaImage::new(3, 1);
+        strip.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        strip.put_pixel(1, 0, Rgba([0, 255, 0, 255]));
+        strip.put_pixel(2, 0, Rgba([0, 0, 255, 255]));
+
+        // A quarter turn stands the strip up around its centre
+        let turn = LayerTransform { rotation: 90.0, ..Default::default() };
+        let mut canvas = RgbaImage::new(3, 3);
+        draw_layer(&mut canvas, &strip, turn.placement((0.0, 1.0, 3.0, 1.0), (3.0, 1.0)), 1.0, BlendMode::Normal);
+        assert_eq!(canvas.get_pixel(1, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(canvas.get_pixel(1, 1), &Rgba([0, 255, 0, 255]));
+        assert_eq!(canvas.get_pixel(1, 2), &Rgba([0, 0, 255, 255]));
+        assert_eq!(canvas.pixels().filter(|p| p[3] > 0).count(), 3);
+
+        let flip = LayerTransform { flip_x: true, ..Default::default() };
+        let mut canvas = RgbaImage::new(3, 1);
+        draw_layer(&mut canvas, &strip, flip.placement((0.0, 0.0, 3.0, 1.0), (3.0, 1.0)), 1.0, BlendMode::Normal);
+        assert_eq!(canvas.get_pixel(0, 0), &Rgba([0, 0, 255, 255]));
+        assert_eq!(canvas.get_pixel(2, 0), &Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn rotated_edges_are_antialiased() {
+        let square = RgbaImage::from_pixel(8, 8, Rgba([255, 255, 255, 255]));
+        let turn = LayerTransform { rotation: 45.0, ..Default::default() };
+        let mut canvas = RgbaImage::new(16, 16);
+        draw_layer(&mut canvas, &square, turn.placement((4.0, 4.0, 8.0, 8.0), (8.0, 8.0)), 1.0, BlendMode::Normal);
+
+        assert_eq!(canvas.get_pixel(8, 8)[3], 255);
+        assert_eq!(canvas.get_pixel(0, 0)[3], 0);
+        let partial = canvas.pixels().filter(|p| p[3] > 0 && p[3] < 255).count();
+        assert!(partial > 0, "expected soft edges");
+        // Colour never darkens towards the edge
+        assert!(canvas.pixels().filter(|p| p[3] > 0).all(|p| p[0] == 255));
+    }
+
+    #[test]
+    fn flatten_fills_transparency_with_background() {
+        let image = RgbaImage::from_pixel(1, 1, CLEAR);
+        assert_eq!(flatten(&image, Rgb([255, 255, 255])).get_pixel(0, 0), &Rgb([255, 255, 255]));
+    }
+}
@@ -0,0 +1,78 @@
+// BananaSlice - Layer Resampling
+// Bilinear sampling of layer pixels in premultiplied space, as the canvas filters images
+
+use super::to_u8;
+use image::{Rgba, RgbaImage};
+
+/// A pixel as premultiplied sRGB-encoded RGB plus alpha, all 0..=1
+pub(super) type Sample = [f32; 4];
+
+/// The pixel at `(x, y)`, or transparent outside the image
+fn texel(image: &RgbaImage, x: i64, y: i64) -> Sample {
+    if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+        return [0.0; 4];
+    }
+    let pixel = image.get_pixel(x as u32, y as u32);
+    let alpha = pixel[3] as f32 / 255.0;
+    let channel = |i: usize| pixel[i] as f32 / 255.0 * alpha;
+    [channel(0), channel(1), channel(2), alpha]
+}
+
+/// Sample `image` at a fractional pixel position, where integer positions land
+/// exactly on pixels
+///
+/// Neighbours outside the image count as transparent, so edges fade out over one
+/// pixel instead of stepping.
+pub(super) fn sample_bilinear(image: &RgbaImage, u: f64, v: f64) -> Sample {
+    let (x0, y0) = (u.floor(), v.floor());
+    let (fx, fy) = ((u - x0) as f32, (v - y0) as f32);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    let mut sample = [0.0; 4];
+    for (x, y, weight) in [
+        (x0, y0, (1.0 - fx) * (1.0 - fy)),
+        (x0 + 1, y0, fx * (1.0 - fy)),
+        (x0, y0 + 1, (1.0 - fx) * fy),
+        (x0 + 1, y0 + 1, fx * fy),
+    ] {
+        if weight == 0.0 {
+            continue;
+        }
+        let texel = texel(image, x, y);
+        for i in 0..4 {
+            sample[i] += texel[i] * weight;
+        }
+    }
+    sample
+}
+
+/// Back to a straight-alpha pixel
+pub(super) fn to_rgba(sample: Sample) -> Rgba<u8> {
+    let alpha = sample[3];
+    if alpha <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    Rgba([
+        to_u8(sample[0] / alpha),
+        to_u8(sample[1] / alpha),
+        to_u8(sample[2] / alpha),
+        to_u8(alpha),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_between_pixels_without_dark_fringes() {
+        let mut image = RgbaImage::new(2, 1);
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+
+        assert_eq!(to_rgba(sample_bilinear(&image, 0.0, 0.0)), Rgba([255, 0, 0, 255]));
+        // Halfway to a transparent pixel: half coverage, still pure red
+        assert_eq!(to_rgba(sample_bilinear(&image, 0.5, 0.0)), Rgba([255, 0, 0, 128]));
+        assert_eq!(to_rgba(sample_bilinear(&image, -0.5, 0.0)), Rgba([255, 0, 0, 128]));
+        assert_eq!(to_rgba(sample_bilinear(&image, 1.0, 0.0)), Rgba([0, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,168 @@
+// BananaSlice - Layer Transforms
+// 2D affine matrices and the rotation, flip and skew a layer is placed with
+
+use serde::{Deserialize, Serialize};
+
+/// A 2D affine matrix in canvas `setTransform(a, b, c, d, e, f)` order:
+/// `x' = a·x + c·y + e`, `y' = b·x + d·y + f`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Affine {
+    pub const IDENTITY: Affine = Affine { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+
+    pub fn translate(x: f64, y: f64) -> Self {
+        Affine { e: x, f: y, ..Self::IDENTITY }
+    }
+
+    pub fn scale(x: f64, y: f64) -> Self {
+        Affine { a: x, d: y, ..Self::IDENTITY }
+    }
+
+    /// Clockwise on screen, since y points down
+    pub fn rotate(degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Affine { a: cos, b: sin, c: -sin, d: cos, ..Self::IDENTITY }
+    }
+
+    pub fn skew_x(degrees: f64) -> Self {
+        Affine { c: degrees.to_radians().tan(), ..Self::IDENTITY }
+    }
+
+    pub fn skew_y(degrees: f64) -> Self {
+        Affine { b: degrees.to_radians().tan(), ..Self::IDENTITY }
+    }
+
+    /// `self · other`: apply `other` first, then `self`
+    pub fn then(self, other: Affine) -> Self {
+        Affine {
+            a: self.a * other.a + self.c * other.b,
+            b: self.b * other.a + self.d * other.b,
+            c: self.a * other.c + self.c * other.d,
+            d: self.b * other.c + self.d * other.d,
+            e: self.a * other.e + self.c * other.f + self.e,
+            f: self.b * other.e + self.d * other.f + self.f,
+        }
+    }
+
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.a * x + self.c * y + self.e, self.b * x + self.d * y + self.f)
+    }
+
+    /// The inverse matrix, or `None` when the transform collapses to a line
+    pub fn invert(&self) -> Option<Self> {
+        let det = self.a * self.d - self.b * self.c;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        Some(Affine {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            e: (self.c * self.f - self.d * self.e) / det,
+            f: (self.b * self.e - self.a * self.f) / det,
+        })
+    }
+
+    /// The translation, when that is all the matrix does
+    pub fn as_translation(&self) -> Option<(f64, f64)> {
+        (self.a == 1.0 && self.b == 0.0 && self.c == 0.0 && self.d == 1.0).then_some((self.e, self.f))
+    }
+}
+
+/// Rotation, flip and skew of a layer, applied about the centre of its box
+///
+/// Matches the canvas object model: angles are in degrees, rotation is clockwise,
+/// and skew is measured in the layer's own pixels before it is scaled to its box.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayerTransform {
+    pub rotation: f64,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub skew_x: f64,
+    pub skew_y: f64,
+}
+
+impl LayerTransform {
+    /// Map from the pixels of a `source_width`×`source_height` image to the canvas, for
+    /// a layer whose untransformed box is `width`×`height` at `(x, y)`
+    pub fn placement(
+        &self,
+        (x, y, width, height): (f64, f64, f64, f64),
+        (source_width, source_height): (f64, f64),
+    ) -> Affine {
+        let flip = |flipped: bool| if flipped { -1.0 } else { 1.0 };
+        Affine::translate(x + width / 2.0, y + height / 2.0)
+            .then(Affine::rotate(self.rotation))
+            .then(Affine::scale(
+                width / source_width * flip(self.flip_x),
+                height / source_height * flip(self.flip_y),
+            ))
+            .then(Affine::skew_x(self.skew_x))
+            .then(Affine::skew_y(self.skew_y))
+            .then(Affine::translate(-source_width / 2.0, -source_height / 2.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_point(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-9 && (actual.1 - expected.1).abs() < 1e-9,
+            "got {:?}, expected {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn composes_and_inverts() {
+        let m = Affine::translate(10.0, 5.0).then(Affine::rotate(90.0)).then(Affine::scale(2.0, 3.0));
+        assert_point(m.apply(1.0, 0.0), (10.0, 7.0));
+        assert_point(m.apply(0.0, 1.0), (7.0, 5.0));
+
+        let inverse = m.invert().unwrap();
+        assert_point(inverse.apply(10.0, 7.0), (1.0, 0.0));
+        assert_point(m.then(inverse).apply(-4.0, 9.0), (-4.0, 9.0));
+
+        assert!(Affine::scale(0.0, 1.0).invert().is_none());
+        assert_eq!(Affine::translate(1.5, -2.0).as_translation(), Some((1.5, -2.0)));
+        assert_eq!(Affine::rotate(30.0).as_translation(), None);
+    }
+
+    #[test]
+    fn placement_transforms_about_the_box_centre() {
+        let bounds = (10.0, 20.0, 40.0, 20.0);
+        let source = (20.0, 10.0);
+
+        // No transform just scales the source into its box
+        let plain = LayerTransform::default().placement(bounds, source);
+        assert_point(plain.apply(0.0, 0.0), (10.0, 20.0));
+        assert_point(plain.apply(20.0, 10.0), (50.0, 40.0));
+
+        // Flipping mirrors within the same box
+        let flipped = LayerTransform { flip_x: true, ..Default::default() }.placement(bounds, source);
+        assert_point(flipped.apply(0.0, 0.0), (50.0, 20.0));
+
+        // A half turn lands the top-left corner on the bottom-right
+        let turned = LayerTransform { rotation: 180.0, ..Default::default() }.placement(bounds, source);
+        assert_point(turned.apply(0.0, 0.0), (50.0, 40.0));
+        assert_point(turned.apply(10.0, 5.0), (30.0, 30.0));
+
+        // Skew shears horizontally in proportion to the distance from the centre
+        let skewed = LayerTransform { skew_x: 45.0, ..Default::default() }.placement(bounds, source);
+        assert_point(skewed.apply(10.0, 0.0), (20.0, 20.0));
+        assert_point(skewed.apply(10.0, 10.0), (40.0, 40.0));
+    }
+}
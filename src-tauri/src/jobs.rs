@@ -0,0 +1,206 @@
+// Generation Job Registry
+// Tracks in-flight generation tasks so they can be listed and cancelled
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::{AbortHandle, JoinHandle};
+
+/// Milliseconds since the Unix epoch
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Public description of a pending job
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub provider: String,
+    pub model: String,
+    pub started_at: u64,
+}
+
+/// How a job finished when its result was collected
+pub enum JobOutcome<T> {
+    Finished(T),
+    Cancelled,
+    Unknown,
+}
+
+/// How long a finished job that nobody is waiting for keeps its result
+const FINISHED_RETENTION: Duration = Duration::from_secs(10 * 60);
+
+struct JobEntry<T> {
+    info: JobInfo,
+    abort: AbortHandle,
+    handle: Option<JoinHandle<T>>,
+    /// When the job was first seen finished with nobody waiting for it
+    finished_at: Option<Instant>,
+}
+
+/// Registry of spawned generation tasks, kept in Tauri managed state
+pub struct JobRegistry<T> {
+    jobs: Mutex<HashMap<String, JobEntry<T>>>,
+    next_id: AtomicU64,
+    retention: Duration,
+}
+
+impl<T> Default for JobRegistry<T> {
+    fn default() -> Self {
+        Self {
+            jobs: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            retention: FINISHED_RETENTION,
+        }
+    }
+}
+
+/// Drop jobs that finished, or were cancelled, more than `retention` ago without being
+/// awaited. Jobs started with `start_generation` may be collected later, so results are
+/// not dropped as soon as they are ready.
+fn evict_finished<T>(jobs: &mut HashMap<String, JobEntry<T>>, retention: Duration) {
+    jobs.retain(|_, entry| {
+        if entry.handle.is_none() || !entry.abort.is_finished() {
+            return true;
+        }
+        entry.finished_at.get_or_insert_with(Instant::now).elapsed() < retention
+    });
+}
+
+impl<T: Send + 'static> JobRegistry<T> {
+    /// Reserve a new job ID
+    pub fn next_id(&self) -> String {
+        format!("gen-{}", self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Spawn a job's future and register it under `info.id`
+    pub fn spawn<F>(&self, info: JobInfo, future: F)
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        let handle = tokio::spawn(future);
+        let entry = JobEntry {
+            info: info.clone(),
+            abort: handle.abort_handle(),
+            handle: Some(handle),
+            finished_at: None,
+        };
+        let mut jobs = self.jobs.lock().unwrap();
+        evict_finished(&mut jobs, self.retention);
+        jobs.insert(info.id, entry);
+    }
+
+    /// Abort a job; dropping its future aborts any in-flight HTTP request
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.jobs.lock().unwrap().get(id) {
+            Some(entry) if !entry.abort.is_finished() => {
+                entry.abort.abort();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Jobs that are still running
+    pub fn pending(&self) -> Vec<JobInfo> {
+        let mut jobs = self.jobs.lock().unwrap();
+        evict_finished(&mut jobs, self.retention);
+        let mut pending: Vec<JobInfo> = jobs
+            .values()
+            .filter(|entry| !entry.abort.is_finished())
+            .map(|entry| entry.info.clone())
+            .collect();
+        pending.sort_by_key(|info| info.started_at);
+        pending
+    }
+
+    /// Wait for a job to finish and remove it from the registry
+    pub async fn wait(&self, id: &str) -> JobOutcome<T> {
+        let handle = match self.jobs.lock().unwrap().get_mut(id) {
+            Some(entry) => entry.handle.take(),
+            None => None,
+        };
+        let Some(handle) = handle else {
+            return JobOutcome::Unknown;
+        };
+
+        let result = handle.await;
+        self.jobs.lock().unwrap().remove(id);
+
+        match result {
+            Ok(value) => JobOutcome::Finished(value),
+            Err(e) if e.is_cancelled() => JobOutcome::Cancelled,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn info(registry: &JobRegistry<u32>) -> JobInfo {
+        JobInfo {
+            id: registry.next_id(),
+            provider: "gemini".to_string(),
+            model: "nano-banana".to_string(),
+            started_at: now_ms(),
+        }
+    }
+
+    #[tokio::test]
+    async fn finished_job_returns_its_value() {
+        let registry = JobRegistry::default();
+        let job = info(&registry);
+        registry.spawn(job.clone(), async { 7 });
+
+        assert!(matches!(registry.wait(&job.id).await, JobOutcome::Finished(7)));
+        assert!(matches!(registry.wait(&job.id).await, JobOutcome::Unknown));
+    }
+
+    #[tokio::test]
+    async fn cancelled_job_resolves_as_cancelled() {
+        let registry = JobRegistry::default();
+        let job = info(&registry);
+        registry.spawn(job.clone(), async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            1
+        });
+
+        assert_eq!(registry.pending().len(), 1);
+        assert!(registry.cancel(&job.id));
+        assert!(matches!(registry.wait(&job.id).await, JobOutcome::Cancelled));
+        assert!(registry.pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn jobs_nobody_awaits_are_evicted() {
+        let registry = JobRegistry { retention: Duration::ZERO, ..JobRegistry::default() };
+        let job = info(&registry);
+        registry.spawn(job.clone(), async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            1
+        });
+
+        assert!(registry.cancel(&job.id));
+        tokio::task::yield_now().await;
+        assert!(registry.pending().is_empty());
+        assert!(matches!(registry.wait(&job.id).await, JobOutcome::Unknown));
+
+        // Within the retention period a finished job can still be collected
+        let registry = JobRegistry::default();
+        let job = info(&registry);
+        registry.spawn(job.clone(), async { 3 });
+        tokio::task::yield_now().await;
+        registry.pending();
+        registry.spawn(info(&registry), async { 4 });
+        assert!(matches!(registry.wait(&job.id).await, JobOutcome::Finished(3)));
+    }
+}
@@ -0,0 +1,246 @@
+// Encrypted Credentials File
+// Fallback secret storage for systems without a keychain service:
+// a JSON file whose secrets are sealed with ChaCha20-Poly1305 under an Argon2id key
+
+use super::KeyringError;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File name inside the app config directory
+pub const FILE_NAME: &str = "credentials.enc.json";
+
+const FORMAT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+
+/// Argon2id cost parameters, stored alongside the salt so they can be raised later
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// OWASP's recommended Argon2id baseline
+    fn default() -> Self {
+        Self { memory_kib: 19 * 1024, iterations: 2, parallelism: 1 }
+    }
+}
+
+/// On-disk layout; everything except the KDF inputs is encrypted
+#[derive(Serialize, Deserialize)]
+struct FileContents {
+    version: u32,
+    kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// An unlocked credentials file
+pub struct EncryptedFile {
+    path: PathBuf,
+    kdf: KdfParams,
+    salt: Vec<u8>,
+    key: Key,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], kdf: KdfParams) -> Result<Key, KeyringError> {
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| KeyringError::FileError(format!("Invalid key derivation parameters: {}", e)))?;
+    let mut key = Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| KeyringError::FileError(format!("Key derivation failed: {}", e)))?;
+    Ok(key)
+}
+
+fn decode(field: &str, value: &str) -> Result<Vec<u8>, KeyringError> {
+    STANDARD
+        .decode(value)
+        .map_err(|e| KeyringError::FileError(format!("Corrupt credentials file ({}): {}", field, e)))
+}
+
+/// Create a file only the current user can read (the mode is set before any secret is written)
+fn create_private(path: &Path) -> std::io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+impl EncryptedFile {
+    /// Create an empty credentials file protected by `passphrase`
+    pub fn create(path: &Path, passphrase: &str, kdf: KdfParams) -> Result<Self, KeyringError> {
+        if passphrase.is_empty() {
+            return Err(KeyringError::FileError("Passphrase cannot be empty".to_string()));
+        }
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+
+        let file = Self {
+            path: path.to_path_buf(),
+            kdf,
+            key: derive_key(passphrase, &salt, kdf)?,
+            salt,
+        };
+        file.write(&BTreeMap::new())?;
+        Ok(file)
+    }
+
+    /// Open an existing file; fails if the passphrase is wrong
+    pub fn unlock(path: &Path, passphrase: &str) -> Result<Self, KeyringError> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| KeyringError::FileError(format!("Cannot read credentials file: {}", e)))?;
+        let contents: FileContents = serde_json::from_str(&json)
+            .map_err(|e| KeyringError::FileError(format!("Corrupt credentials file: {}", e)))?;
+        if contents.version != FORMAT_VERSION {
+            return Err(KeyringError::FileError(format!(
+                "Unsupported credentials file version {}",
+                contents.version
+            )));
+        }
+
+        let salt = decode("salt", &contents.salt)?;
+        let file = Self {
+            path: path.to_path_buf(),
+            kdf: contents.kdf,
+            key: derive_key(passphrase, &salt, contents.kdf)?,
+            salt,
+        };
+        file.read()?;
+        Ok(file)
+    }
+
+    /// Decrypt every stored secret, keyed by account
+    fn read(&self) -> Result<BTreeMap<String, String>, KeyringError> {
+        let json = fs::read_to_string(&self.path)
+            .map_err(|e| KeyringError::FileError(format!("Cannot read credentials file: {}", e)))?;
+        let contents: FileContents = serde_json::from_str(&json)
+            .map_err(|e| KeyringError::FileError(format!("Corrupt credentials file: {}", e)))?;
+
+        let nonce = decode("nonce", &contents.nonce)?;
+        let ciphertext = decode("ciphertext", &contents.ciphertext)?;
+        if nonce.len() != 12 {
+            return Err(KeyringError::FileError("Corrupt credentials file (nonce)".to_string()));
+        }
+        let plaintext = ChaCha20Poly1305::new(&self.key)
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| KeyringError::WrongPassphrase)?;
+        serde_json::from_slice(&plaintext)
+            .map_err(|e| KeyringError::FileError(format!("Corrupt credentials file: {}", e)))
+    }
+
+    /// Re-encrypt all secrets under a fresh nonce
+    fn write(&self, secrets: &BTreeMap<String, String>) -> Result<(), KeyringError> {
+        let plaintext = serde_json::to_vec(secrets)
+            .map_err(|e| KeyringError::FileError(format!("Failed to encode secrets: {}", e)))?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = ChaCha20Poly1305::new(&self.key)
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| KeyringError::FileError("Encryption failed".to_string()))?;
+
+        let contents = FileContents {
+            version: FORMAT_VERSION,
+            kdf: self.kdf,
+            salt: STANDARD.encode(&self.salt),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+        let json = serde_json::to_string_pretty(&contents)
+            .map_err(|e| KeyringError::FileError(format!("Failed to encode credentials file: {}", e)))?;
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| KeyringError::FileError(format!("Cannot create config directory: {}", e)))?;
+        }
+        // Write then rename so a crash never leaves a half-written file
+        let temp = self.path.with_extension("tmp");
+        let _ = fs::remove_file(&temp);
+        create_private(&temp)
+            .and_then(|mut file| file.write_all(json.as_bytes()).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temp, &self.path))
+            .map_err(|e| KeyringError::FileError(format!("Cannot write credentials file: {}", e)))
+    }
+
+    pub fn get(&self, account: &str) -> Result<Option<String>, KeyringError> {
+        Ok(self.read()?.remove(account))
+    }
+
+    pub fn set(&self, account: &str, secret: &str) -> Result<(), KeyringError> {
+        let mut secrets = self.read()?;
+        secrets.insert(account.to_string(), secret.to_string());
+        self.write(&secrets)
+    }
+
+    pub fn delete(&self, account: &str) -> Result<(), KeyringError> {
+        let mut secrets = self.read()?;
+        if secrets.remove(account).is_some() {
+            self.write(&secrets)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap parameters so tests do not spend seconds in Argon2
+    const TEST_KDF: KdfParams = KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 };
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("bananaslice-credentials-{}-{}", name, std::process::id()))
+            .join(FILE_NAME)
+    }
+
+    #[test]
+    fn secrets_round_trip_and_are_not_stored_in_plaintext() {
+        let path = temp_path("round-trip");
+        let file = EncryptedFile::create(&path, "correct horse", TEST_KDF).unwrap();
+        file.set("Gemini-Key", "AIza-very-secret").unwrap();
+
+        let reopened = EncryptedFile::unlock(&path, "correct horse").unwrap();
+        assert_eq!(reopened.get("Gemini-Key").unwrap().as_deref(), Some("AIza-very-secret"));
+        assert!(!fs::read_to_string(&path).unwrap().contains("AIza-very-secret"));
+
+        reopened.delete("Gemini-Key").unwrap();
+        assert_eq!(reopened.get("Gemini-Key").unwrap(), None);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn wrong_passphrase_is_rejected() {
+        let path = temp_path("wrong-passphrase");
+        EncryptedFile::create(&path, "correct horse", TEST_KDF).unwrap();
+
+        let result = EncryptedFile::unlock(&path, "battery staple");
+        assert!(matches!(result, Err(KeyringError::WrongPassphrase)));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_is_only_readable_by_its_owner() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = temp_path("permissions");
+        EncryptedFile::create(&path, "correct horse", TEST_KDF).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
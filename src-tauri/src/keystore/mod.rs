@@ -0,0 +1,441 @@
+// Secure API Key Storage using OS Native Keychain
+// Uses the 'keyring' crate to interface with:
+// - Windows Credential Manager
+// - macOS Keychain
+// - Linux libsecret/KWallet
+// Systems without any of these can opt into an encrypted credentials file instead.
+
+mod encrypted_file;
+
+use crate::profiles::DEFAULT_PROFILE_ID;
+use encrypted_file::{EncryptedFile, KdfParams};
+use keyring::Entry;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use thiserror::Error;
+
+const SERVICE_NAME: &str = "BananaSlice-API";
+const USER_ACCOUNT: &str = "Gemini-Key";
+const PROXY_ACCOUNT: &str = "Network-Proxy";
+
+// Secret ID of the HTTP proxy password; profile IDs are slugs, so it cannot collide
+pub const PROXY_SECRET_ID: &str = "network:proxy";
+
+#[derive(Error, Debug)]
+pub enum KeyringError {
+    #[error("Failed to access system keychain: {0}")]
+    KeychainError(String),
+
+    #[error("API key not found")]
+    KeyNotFound,
+
+    #[error("Credentials file error: {0}")]
+    FileError(String),
+
+    #[error("Incorrect passphrase for the encrypted credentials file")]
+    WrongPassphrase,
+
+    #[error("The encrypted credentials file is locked. Enter your passphrase in Settings to unlock it.")]
+    Locked,
+}
+
+// Where secrets are stored
+enum Backend {
+    Keychain,
+    // Explicitly selected fallback; `file` is None until unlocked this session
+    EncryptedFile { path: PathBuf, file: Option<EncryptedFile> },
+}
+
+static BACKEND: Mutex<Backend> = Mutex::new(Backend::Keychain);
+
+fn backend() -> MutexGuard<'static, Backend> {
+    BACKEND.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Active storage backend, reported to Settings so the user knows where secrets live
+#[derive(Debug, Serialize)]
+pub struct BackendStatus {
+    pub backend: &'static str, // "keychain" or "encrypted_file"
+    pub locked: bool,
+    pub path: Option<String>,
+}
+
+// Select the encrypted file backend at startup if the user chose it earlier
+pub fn init(config_dir: &Path) {
+    let path = config_dir.join(encrypted_file::FILE_NAME);
+    if path.exists() {
+        log::warn!("Using encrypted credentials file instead of the system keychain: {:?}", path);
+        *backend() = Backend::EncryptedFile { path, file: None };
+    }
+}
+
+// Switch to the encrypted file backend, creating the file or unlocking an existing one
+pub fn use_encrypted_file(config_dir: &Path, passphrase: &str) -> Result<(), KeyringError> {
+    let path = config_dir.join(encrypted_file::FILE_NAME);
+    let file = if path.exists() {
+        EncryptedFile::unlock(&path, passphrase)?
+    } else {
+        EncryptedFile::create(&path, passphrase, KdfParams::default())?
+    };
+
+    log::warn!("Secrets will be stored in an encrypted file instead of the system keychain: {:?}", path);
+    *backend() = Backend::EncryptedFile { path, file: Some(file) };
+    Ok(())
+}
+
+// Unlock the encrypted file backend for this session
+pub fn unlock_encrypted_file(passphrase: &str) -> Result<(), KeyringError> {
+    let path = match &*backend() {
+        Backend::EncryptedFile { path, .. } => path.clone(),
+        Backend::Keychain => {
+            return Err(KeyringError::FileError("The encrypted credentials file is not in use".to_string()))
+        }
+    };
+    // Derive the key without holding the lock, so other secret lookups are not stalled
+    let unlocked = EncryptedFile::unlock(&path, passphrase)?;
+    match &mut *backend() {
+        Backend::EncryptedFile { path: current, file } if *current == path => {
+            *file = Some(unlocked);
+            log::info!("Encrypted credentials file unlocked");
+            Ok(())
+        }
+        _ => Err(KeyringError::FileError("The encrypted credentials file is not in use".to_string())),
+    }
+}
+
+// Go back to the system keychain; secrets in the encrypted file are deleted with it
+pub fn use_keychain() -> Result<(), KeyringError> {
+    let mut backend = backend();
+    if let Backend::EncryptedFile { path, .. } = &*backend {
+        std::fs::remove_file(path)
+            .map_err(|e| KeyringError::FileError(format!("Cannot remove credentials file: {}", e)))?;
+        log::info!("Encrypted credentials file removed; using the system keychain");
+    }
+    *backend = Backend::Keychain;
+    Ok(())
+}
+
+pub fn backend_status() -> BackendStatus {
+    match &*backend() {
+        Backend::Keychain => BackendStatus { backend: "keychain", locked: false, path: None },
+        Backend::EncryptedFile { path, file } => BackendStatus {
+            backend: "encrypted_file",
+            locked: file.is_none(),
+            path: Some(path.display().to_string()),
+        },
+    }
+}
+
+// Keychain account for a profile; the default profile keeps the original single-key entry
+fn account_for(profile_id: &str) -> String {
+    match profile_id {
+        DEFAULT_PROFILE_ID => USER_ACCOUNT.to_string(),
+        PROXY_SECRET_ID => PROXY_ACCOUNT.to_string(),
+        _ => format!("Profile-{}", profile_id),
+    }
+}
+
+// Helper to get the keyring entry for a profile
+fn get_entry(profile_id: &str) -> Result<Entry, KeyringError> {
+    Entry::new(SERVICE_NAME, &account_for(profile_id))
+        .map_err(|e| KeyringError::KeychainError(format!("Failed to create keyring entry: {}", e)))
+}
+
+// Store a profile's secret in the active backend
+pub fn store_secret(profile_id: &str, secret: &str) -> Result<(), KeyringError> {
+    match &*backend() {
+        Backend::EncryptedFile { file: Some(file), .. } => {
+            file.set(&account_for(profile_id), secret)?;
+            log::info!("Secret for profile '{}' saved to encrypted credentials file", profile_id);
+            return Ok(());
+        }
+        Backend::EncryptedFile { file: None, .. } => return Err(KeyringError::Locked),
+        Backend::Keychain => {}
+    }
+
+    let entry = get_entry(profile_id)?;
+    entry.set_password(secret).map_err(|e| {
+        KeyringError::KeychainError(format!(
+            "Failed to set password: {}. If this system has no keychain service, \
+             switch to the encrypted credentials file in Settings.",
+            e
+        ))
+    })?;
+
+    log::info!("Secret for profile '{}' securely saved to system keychain", profile_id);
+
+    // Immediate verification for debugging
+    match entry.get_password() {
+        Ok(_) => log::info!("API key persistence verified"),
+        Err(e) => log::error!("API key saved but verification failed: {}", e),
+    }
+
+    Ok(())
+}
+
+// Retrieve a profile's secret from the active backend
+pub fn get_secret(profile_id: &str) -> Result<String, KeyringError> {
+    match &*backend() {
+        Backend::EncryptedFile { file: Some(file), .. } => {
+            return file
+                .get(&account_for(profile_id))?
+                .map(|key| key.trim().to_string())
+                .filter(|key| !key.is_empty())
+                .ok_or(KeyringError::KeyNotFound);
+        }
+        Backend::EncryptedFile { file: None, .. } => return Err(KeyringError::Locked),
+        Backend::Keychain => {}
+    }
+
+    let entry = get_entry(profile_id)?;
+
+    match entry.get_password() {
+        Ok(key) => {
+            let key = key.trim().to_string();
+            if key.is_empty() {
+                Err(KeyringError::KeyNotFound)
+            } else {
+                Ok(key)
+            }
+        }
+        Err(e) => {
+            log::debug!("Keychain check: {}", e);
+            Err(KeyringError::KeyNotFound)
+        }
+    }
+}
+
+// Delete a profile's secret from the active backend
+pub fn delete_secret(profile_id: &str) -> Result<(), KeyringError> {
+    match &*backend() {
+        Backend::EncryptedFile { file: Some(file), .. } => return file.delete(&account_for(profile_id)),
+        Backend::EncryptedFile { file: None, .. } => return Err(KeyringError::Locked),
+        Backend::Keychain => {}
+    }
+
+    let entry = get_entry(profile_id)?;
+    
+    // We ignore error on delete if key wasn't there
+    let _ = entry.delete_credential();
+    
+    Ok(())
+}
+
+// Check if a profile has a secret stored
+pub fn has_secret(profile_id: &str) -> bool {
+    get_secret(profile_id).is_ok()
+}
+
+// Store the API key of the default (pre-profiles) entry
+pub fn store_api_key(api_key: &str) -> Result<(), KeyringError> {
+    store_secret(DEFAULT_PROFILE_ID, api_key)
+}
+
+// Delete the API key of the default (pre-profiles) entry
+pub fn delete_api_key() -> Result<(), KeyringError> {
+    delete_secret(DEFAULT_PROFILE_ID)
+}
+
+// Check if the default (pre-profiles) API key exists
+pub fn has_api_key() -> bool {
+    has_secret(DEFAULT_PROFILE_ID)
+}
+
+// Environment variables checked after an explicit profile
+const ENV_API_KEY: &str = "BANANASLICE_API_KEY";
+const ENV_API_KEY_FILE: &str = "BANANASLICE_API_KEY_FILE";
+const ENV_GEMINI_API_KEY: &str = "GEMINI_API_KEY";
+const ENV_OPENAI_API_KEY: &str = "BANANASLICE_OPENAI_API_KEY";
+const ENV_OPENAI_API_KEY_FILE: &str = "BANANASLICE_OPENAI_API_KEY_FILE";
+
+// A variable holding a key, or naming a file that holds one
+enum EnvKey {
+    Value(&'static str),
+    File(&'static str),
+}
+
+// Variables a provider reads its key from, in order. Only providers that take a plain
+// API key have any: a Gemini key must not reach an OpenAI-compatible endpoint, and
+// Vertex needs a service-account JSON rather than a key.
+fn env_keys(provider: &str) -> &'static [EnvKey] {
+    match provider {
+        "gemini" => &[EnvKey::Value(ENV_API_KEY), EnvKey::File(ENV_API_KEY_FILE), EnvKey::Value(ENV_GEMINI_API_KEY)],
+        "openai" => &[EnvKey::Value(ENV_OPENAI_API_KEY), EnvKey::File(ENV_OPENAI_API_KEY_FILE)],
+        _ => &[],
+    }
+}
+
+// Where a resolved key came from (the value itself is never reported)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum KeySource {
+    // The profile named in the request
+    Profile { profile_id: String },
+    // An environment variable, or the file named by a *_FILE variable
+    Environment { variable: String },
+    // The default profile's entry in the keychain (or encrypted file)
+    Keyring { profile_id: String },
+}
+
+pub struct ResolvedKey {
+    pub secret: String,
+    pub source: KeySource,
+}
+
+// Read a provider's key from the environment
+fn key_from_env(provider: &str, var: impl Fn(&str) -> Option<String>) -> Option<ResolvedKey> {
+    let non_empty = |value: String| Some(value.trim().to_string()).filter(|v| !v.is_empty());
+    env_keys(provider).iter().find_map(|key| {
+        let (name, secret) = match *key {
+            EnvKey::Value(name) => (name, var(name).and_then(non_empty)),
+            EnvKey::File(name) => {
+                let path = var(name)?;
+                match std::fs::read_to_string(&path) {
+                    Ok(contents) => (name, non_empty(contents)),
+                    Err(e) => {
+                        log::warn!("{} is set but {} cannot be read: {}", name, path, e);
+                        return None;
+                    }
+                }
+            }
+        };
+        secret.map(|secret| ResolvedKey { secret, source: KeySource::Environment { variable: name.to_string() } })
+    })
+}
+
+// Resolve a key: explicit profile, then environment, then the stored default.
+// A locked credentials file is only reported when nothing else supplies a key,
+// so the user is told to unlock it rather than that no key is configured.
+pub fn resolve_api_key(
+    explicit_profile: Option<&str>,
+    stored_profile: Option<&str>,
+    provider: &str,
+) -> Result<Option<ResolvedKey>, KeyringError> {
+    let mut locked = false;
+    if let Some(profile_id) = explicit_profile {
+        match get_secret(profile_id) {
+            Ok(secret) => {
+                return Ok(Some(ResolvedKey { secret, source: KeySource::Profile { profile_id: profile_id.to_string() } }))
+            }
+            Err(KeyringError::Locked) => locked = true,
+            Err(_) => {}
+        }
+    }
+
+    if let Some(resolved) = key_from_env(provider, |name| std::env::var(name).ok()) {
+        return Ok(Some(resolved));
+    }
+
+    if let Some(profile_id) = stored_profile {
+        match get_secret(profile_id) {
+            Ok(secret) => {
+                return Ok(Some(ResolvedKey { secret, source: KeySource::Keyring { profile_id: profile_id.to_string() } }))
+            }
+            Err(KeyringError::Locked) => locked = true,
+            Err(_) => {}
+        }
+    }
+    if locked {
+        return Err(KeyringError::Locked);
+    }
+    Ok(None)
+}
+
+// Shown when a provider that needs a key has none, naming where it can come from
+pub fn missing_key_message(provider: &str) -> String {
+    let variables: Vec<&str> = env_keys(provider)
+        .iter()
+        .filter_map(|key| match *key {
+            EnvKey::Value(name) => Some(name),
+            EnvKey::File(_) => None,
+        })
+        .collect();
+    match variables.as_slice() {
+        [] => format!("No API key configured for {}. Add one to a credential profile in Settings.", provider),
+        names => format!(
+            "No API key configured for {}. Add one in Settings or set the {} environment variable.",
+            provider,
+            names.join(" or ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn source(resolved: Option<ResolvedKey>) -> Option<KeySource> {
+        resolved.map(|r| r.source)
+    }
+
+    #[test]
+    fn bananaslice_variable_wins_over_gemini_variable() {
+        let vars = env(&[(ENV_API_KEY, " app-key "), (ENV_GEMINI_API_KEY, "gemini-key")]);
+        let resolved = key_from_env("gemini", vars).unwrap();
+        assert_eq!(resolved.secret, "app-key");
+        assert_eq!(resolved.source, KeySource::Environment { variable: ENV_API_KEY.to_string() });
+    }
+
+    #[test]
+    fn gemini_variable_only_applies_to_gemini() {
+        let vars = env(&[(ENV_GEMINI_API_KEY, "gemini-key"), (ENV_API_KEY, "  ")]);
+        assert_eq!(
+            source(key_from_env("gemini", &vars)),
+            Some(KeySource::Environment { variable: ENV_GEMINI_API_KEY.to_string() })
+        );
+        assert_eq!(source(key_from_env("openai", &vars)), None);
+    }
+
+    #[test]
+    fn key_file_is_read_and_trimmed() {
+        let path = std::env::temp_dir().join(format!("bananaslice-key-{}", std::process::id()));
+        std::fs::write(&path, "file-key\n").unwrap();
+        let path_str = path.display().to_string();
+
+        let resolved = key_from_env("gemini", env(&[(ENV_API_KEY_FILE, &path_str)])).unwrap();
+        assert_eq!(resolved.secret, "file-key");
+        assert_eq!(resolved.source, KeySource::Environment { variable: ENV_API_KEY_FILE.to_string() });
+
+        let resolved = key_from_env("openai", env(&[(ENV_OPENAI_API_KEY_FILE, &path_str)])).unwrap();
+        assert_eq!(resolved.source, KeySource::Environment { variable: ENV_OPENAI_API_KEY_FILE.to_string() });
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn generic_variables_only_apply_to_gemini() {
+        let vars = env(&[(ENV_API_KEY, "app-key"), (ENV_API_KEY_FILE, "/nonexistent"), (ENV_GEMINI_API_KEY, "gemini-key")]);
+        // Vertex needs a service-account JSON, so no key variable may stand in for it
+        assert_eq!(source(key_from_env("vertex", &vars)), None);
+        assert_eq!(source(key_from_env("openai", &vars)), None);
+        assert_eq!(source(key_from_env("automatic1111", &vars)), None);
+
+        let vars = env(&[(ENV_API_KEY, "app-key"), (ENV_OPENAI_API_KEY, "openai-key")]);
+        assert_eq!(key_from_env("openai", &vars).unwrap().secret, "openai-key");
+        assert_eq!(source(key_from_env("vertex", &vars)), None);
+    }
+
+    #[test]
+    fn locked_file_is_reported_instead_of_a_missing_key() {
+        let path = std::env::temp_dir().join("bananaslice-locked-credentials");
+        let previous = std::mem::replace(&mut *backend(), Backend::EncryptedFile { path, file: None });
+
+        // ComfyUI reads no variables, so nothing else can supply a key
+        let result = resolve_api_key(Some("work"), Some(DEFAULT_PROFILE_ID), "comfyui");
+        *backend() = previous;
+        assert!(matches!(result, Err(KeyringError::Locked)));
+    }
+
+    #[test]
+    fn missing_key_message_names_the_provider() {
+        let message = missing_key_message("openai");
+        assert!(message.contains("openai") && message.contains(ENV_OPENAI_API_KEY), "{}", message);
+        assert!(!missing_key_message("automatic1111").contains("Gemini"));
+    }
+}
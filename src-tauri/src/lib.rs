@@ -2,14 +2,23 @@
 
 mod api;
 mod commands;
+mod compositor;
+mod jobs;
 mod keystore;
+mod profiles;
 
 use commands::{
     get_app_info, open_image, save_image,
-    generate_fill, set_api_key, has_api_key, delete_api_key,
-    composite_patch, composite_layers
+    generate_fill, start_generation, await_generation, cancel_generation, list_generations,
+    set_api_key, validate_api_key, has_api_key, get_api_key_source, delete_api_key, list_models,
+    list_profiles, add_profile, rename_profile, delete_profile, set_default_profile,
+    get_network_settings, set_network_settings,
+    get_secret_backend, use_encrypted_secret_file, unlock_secret_file, use_system_keychain,
+    calculate_aspect_ratio_adjustment, composite_patch, composite_layers, GenerationJobs, ModelCache
 };
 
+use tauri::Manager;
+
 #[tauri::command]
 fn show_main_window(window: tauri::Window) {
     window.show().unwrap();
@@ -23,7 +32,13 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_shell::init())
+        .manage(GenerationJobs::default())
+        .manage(ModelCache::default())
         .setup(|app| {
+            // Honor an earlier opt-in to the encrypted credentials file
+            if let Ok(config_dir) = app.path().app_config_dir() {
+                keystore::init(&config_dir);
+            }
             if cfg!(debug_assertions) {
                 app.handle().plugin(
                     tauri_plugin_log::Builder::default()
@@ -38,9 +53,28 @@ pub fn run() {
             open_image,
             save_image,
             generate_fill,
+            start_generation,
+            await_generation,
+            cancel_generation,
+            list_generations,
             set_api_key,
+            validate_api_key,
             has_api_key,
+            get_api_key_source,
             delete_api_key,
+            list_models,
+            list_profiles,
+            add_profile,
+            rename_profile,
+            delete_profile,
+            set_default_profile,
+            get_network_settings,
+            set_network_settings,
+            get_secret_backend,
+            use_encrypted_secret_file,
+            unlock_secret_file,
+            use_system_keychain,
+            calculate_aspect_ratio_adjustment,
             composite_patch,
             composite_layers,
             show_main_window
@@ -0,0 +1,261 @@
+// Credential Profiles
+// Named provider connections; secrets live in the keychain, metadata in profiles.json
+
+use crate::api::AuthStyle;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Profile created for the key stored before profiles existed
+pub const DEFAULT_PROFILE_ID: &str = "default";
+
+#[derive(Error, Debug)]
+pub enum ProfileError {
+    #[error("Profile not found: {0}")]
+    NotFound(String),
+
+    #[error("Profile label cannot be empty")]
+    EmptyLabel,
+
+    #[error("Failed to access profiles file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid profiles file: {0}")]
+    Parse(#[from] serde_json::Error),
+}
+
+/// IDs with their own keychain entries: the pre-profile key and secrets like the proxy password
+fn is_reserved(id: &str) -> bool {
+    id == DEFAULT_PROFILE_ID || id.starts_with("network:")
+}
+
+/// A named provider connection; the secret is stored under its `id` in the keychain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CredentialProfile {
+    pub id: String,
+    pub label: String,
+    /// Provider ID such as "gemini", "vertex" or "openai"
+    pub provider: String,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// How the secret is sent; the provider's convention when unset
+    #[serde(default)]
+    pub auth: Option<AuthStyle>,
+}
+
+/// Contents of profiles.json
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ProfileIndex {
+    pub default_profile: Option<String>,
+    pub profiles: Vec<CredentialProfile>,
+}
+
+impl ProfileIndex {
+    pub fn get(&self, id: &str) -> Option<&CredentialProfile> {
+        self.profiles.iter().find(|profile| profile.id == id)
+    }
+
+    /// The profile to use for a request
+    ///
+    /// An explicit `id` must exist. Otherwise the default profile is used,
+    /// unless the request names a different provider, in which case the first
+    /// profile for that provider is picked (or none at all).
+    pub fn resolve(&self, id: Option<&str>, provider: Option<&str>) -> Result<Option<&CredentialProfile>, ProfileError> {
+        if let Some(id) = id {
+            return self.get(id).map(Some).ok_or_else(|| ProfileError::NotFound(id.to_string()));
+        }
+
+        let default = self.default_profile.as_deref().and_then(|id| self.get(id));
+        Ok(match provider {
+            None => default,
+            Some(provider) => default
+                .filter(|profile| profile.provider == provider)
+                .or_else(|| self.profiles.iter().find(|profile| profile.provider == provider)),
+        })
+    }
+
+    /// Derive a unique, stable ID from a label ("Work Gemini" -> "work-gemini")
+    fn unique_id(&self, label: &str) -> String {
+        let slug: String = label
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect::<String>()
+            .split('-')
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("-");
+        let base = if slug.is_empty() { "profile".to_string() } else { slug };
+
+        let mut id = base.clone();
+        let mut suffix = 2;
+        while self.get(&id).is_some() || is_reserved(&id) {
+            id = format!("{}-{}", base, suffix);
+            suffix += 1;
+        }
+        id
+    }
+
+    /// Add a profile, assigning its ID; the first profile becomes the default
+    pub fn add(
+        &mut self,
+        label: &str,
+        provider: &str,
+        base_url: Option<String>,
+        auth: Option<AuthStyle>,
+    ) -> Result<CredentialProfile, ProfileError> {
+        let label = label.trim();
+        if label.is_empty() {
+            return Err(ProfileError::EmptyLabel);
+        }
+
+        let profile = CredentialProfile {
+            id: self.unique_id(label),
+            label: label.to_string(),
+            provider: provider.to_string(),
+            base_url: base_url.filter(|url| !url.is_empty()),
+            auth,
+        };
+        if self.default_profile.is_none() {
+            self.default_profile = Some(profile.id.clone());
+        }
+        self.profiles.push(profile.clone());
+        Ok(profile)
+    }
+
+    /// Change a profile's label; its ID (and keychain entry) stays the same
+    pub fn rename(&mut self, id: &str, label: &str) -> Result<(), ProfileError> {
+        let label = label.trim();
+        if label.is_empty() {
+            return Err(ProfileError::EmptyLabel);
+        }
+        let profile = self
+            .profiles
+            .iter_mut()
+            .find(|profile| profile.id == id)
+            .ok_or_else(|| ProfileError::NotFound(id.to_string()))?;
+        profile.label = label.to_string();
+        Ok(())
+    }
+
+    /// Remove a profile; if it was the default, the first remaining one takes over
+    pub fn remove(&mut self, id: &str) -> Result<CredentialProfile, ProfileError> {
+        let position = self
+            .profiles
+            .iter()
+            .position(|profile| profile.id == id)
+            .ok_or_else(|| ProfileError::NotFound(id.to_string()))?;
+        let removed = self.profiles.remove(position);
+        if self.default_profile.as_deref() == Some(id) {
+            self.default_profile = self.profiles.first().map(|profile| profile.id.clone());
+        }
+        Ok(removed)
+    }
+
+    pub fn set_default(&mut self, id: &str) -> Result<(), ProfileError> {
+        if self.get(id).is_none() {
+            return Err(ProfileError::NotFound(id.to_string()));
+        }
+        self.default_profile = Some(id.to_string());
+        Ok(())
+    }
+}
+
+/// profiles.json on disk
+pub struct ProfileStore {
+    path: PathBuf,
+}
+
+impl ProfileStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Load the index; a missing file is an empty index
+    pub fn load(&self) -> Result<ProfileIndex, ProfileError> {
+        match fs::read_to_string(&self.path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ProfileIndex::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, index: &ProfileIndex) -> Result<(), ProfileError> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(index)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_unique_slugs_and_first_profile_is_default() {
+        let mut index = ProfileIndex::default();
+        let first = index.add("Work Gemini", "gemini", None, None).unwrap();
+        let second = index.add("Work  Gemini!", "gemini", Some(String::new()), None).unwrap();
+
+        assert_eq!(first.id, "work-gemini");
+        assert_eq!(second.id, "work-gemini-2");
+        assert_eq!(second.base_url, None);
+        assert_eq!(index.default_profile.as_deref(), Some("work-gemini"));
+        assert!(matches!(index.add("  ", "gemini", None, None), Err(ProfileError::EmptyLabel)));
+    }
+
+    #[test]
+    fn reserved_ids_are_never_assigned() {
+        // "default" is the legacy key's account, which set_api_key overwrites
+        let mut index = ProfileIndex::default();
+        assert_eq!(index.add("Default", "gemini", None, None).unwrap().id, "default-2");
+        assert_eq!(index.add("default", "openai", None, None).unwrap().id, "default-3");
+        assert!(is_reserved("network:proxy"));
+    }
+
+    #[test]
+    fn removing_the_default_promotes_the_next_profile() {
+        let mut index = ProfileIndex::default();
+        index.add("Gemini", "gemini", None, None).unwrap();
+        index.add("Gateway", "openai", Some("https://llm.example.com/v1".to_string()), Some(AuthStyle::Bearer)).unwrap();
+
+        index.rename("gateway", "Team gateway").unwrap();
+        index.remove("gemini").unwrap();
+
+        assert_eq!(index.default_profile.as_deref(), Some("gateway"));
+        assert_eq!(index.resolve(None, None).unwrap().unwrap().label, "Team gateway");
+        assert!(matches!(index.resolve(Some("gemini"), None), Err(ProfileError::NotFound(_))));
+        assert!(matches!(index.set_default("missing"), Err(ProfileError::NotFound(_))));
+    }
+
+    #[test]
+    fn resolution_prefers_profiles_for_the_requested_provider() {
+        let mut index = ProfileIndex::default();
+        index.add("Gemini", "gemini", None, None).unwrap();
+        index.add("OpenAI", "openai", None, None).unwrap();
+
+        assert_eq!(index.resolve(None, Some("gemini")).unwrap().unwrap().id, "gemini");
+        assert_eq!(index.resolve(None, Some("openai")).unwrap().unwrap().id, "openai");
+        assert_eq!(index.resolve(Some("openai"), Some("gemini")).unwrap().unwrap().id, "openai");
+        assert!(index.resolve(None, Some("comfyui")).unwrap().is_none());
+    }
+
+    #[test]
+    fn store_round_trips_and_treats_missing_file_as_empty() {
+        let dir = std::env::temp_dir().join(format!("bananaslice-profiles-{}", std::process::id()));
+        let store = ProfileStore::new(dir.join("profiles.json"));
+        assert!(store.load().unwrap().profiles.is_empty());
+
+        let mut index = ProfileIndex::default();
+        index.add("Vertex", "vertex", None, None).unwrap();
+        store.save(&index).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.profiles, index.profiles);
+        assert_eq!(loaded.default_profile.as_deref(), Some("vertex"));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
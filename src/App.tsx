@@ -230,7 +230,7 @@ function App() {
                                     <div className="submenu">
                                         <button onClick={() => { setFileMenuOpen(false); handleExport('png'); }} disabled={!baseImage}>PNG</button>
                                         <button onClick={() => { setFileMenuOpen(false); handleExport('jpeg'); }} disabled={!baseImage}>JPEG</button>
-                                        <button onClick={() => { setFileMenuOpen(false); handleExport('webp'); }} disabled={!baseImage}>WebP</button>
+                                        <button onClick={() => { setFileMenuOpen(false); handleExport('webp'); }} disabled={!baseImage}>WebP (lossless)</button>
                                     </div>
                                 </div>
                             </div>
@@ -1,6 +1,7 @@
 // API bindings for Tauri commands
 import { invoke } from '@tauri-apps/api/core';
-import type { AIModel } from '../types';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { AIModel, BlendMode, Layer } from '../types';
 
 export interface GenerateRequest {
     model: string;
@@ -9,12 +10,178 @@ export interface GenerateRequest {
     mask_base64: string;
     reference_images?: string[]; // Optional reference images as base64
     base_url?: string; // Optional custom API base URL
+    auth?: AuthStyle; // How the key is sent; defaults to the provider's convention
+    provider?: string; // Optional provider ID: 'gemini' (default), 'vertex', 'openai', 'automatic1111' or 'comfyui'
+    profile_id?: string; // Credential profile to use; defaults to the default profile
+    provider_options?: Record<string, unknown>; // Provider-specific options
+    candidate_count?: number; // Number of variants to generate (1-4)
+    retry?: RetryPolicy; // Retry policy for transient failures
+}
+
+// Where the API key goes on each request. 'query' puts it in the URL and is opt-in only.
+export type AuthStyle =
+    | { style: 'goog_api_key' } // x-goog-api-key header (Gemini default)
+    | { style: 'bearer' } // Authorization: Bearer (OpenAI default)
+    | { style: 'header'; name: string } // Custom header name
+    | { style: 'query'; name?: string }; // ?key=... (name defaults to 'key')
+
+export interface CredentialProfile {
+    id: string;
+    label: string;
+    provider: string;
+    base_url: string | null;
+    auth: AuthStyle | null; // Provider convention when null
+}
+
+export interface ProfileInfo extends CredentialProfile {
+    is_default: boolean;
+    has_secret: boolean;
+}
+
+export interface AddProfileRequest {
+    label: string;
+    provider: string;
+    base_url?: string;
+    auth?: AuthStyle;
+    secret?: string; // API key or service-account JSON, stored in the system keychain
+}
+
+export interface SecretBackendStatus {
+    backend: 'keychain' | 'encrypted_file';
+    locked: boolean; // encrypted_file: passphrase not yet entered this session
+    path: string | null; // encrypted_file: location of the credentials file
+}
+
+// HTTP settings shared by every provider; a timeout of 0 disables it
+export interface NetworkSettings {
+    connect_timeout_secs: number; // Default 15
+    read_timeout_secs: number; // Per read, so stalled connections fail (default 180)
+    total_timeout_secs: number; // Whole request (default 300)
+    proxy: { url: string; username?: string | null } | null; // HTTP(S) proxy; password is stored in the keychain
+    root_certificates_pem: string | null; // Extra trusted CAs as a PEM bundle
+    user_agent_suffix: string | null; // Appended to "BananaSlice/<version>"
+}
+
+export interface NetworkSettingsInfo extends NetworkSettings {
+    has_proxy_password: boolean;
+}
+
+// Image-capable model reported by list_models
+export interface ModelInfo {
+    id: string; // Value for GenerateRequest.model
+    display_name: string;
+    alias: string | null; // e.g. 'nano-banana-2' for gemini-3.1-flash-image
+    description: string | null;
+    input_token_limit: number | null;
+    output_token_limit: number | null;
+}
+
+// Outcome of probing a provider with a key
+export type KeyValidation =
+    | { status: 'valid' }
+    | { status: 'invalid'; message: string }
+    | { status: 'quota_exhausted'; message: string; retry_after: number | null } // Key works, no quota left
+    | { status: 'network_unreachable'; message: string } // Key could not be checked
+    | { status: 'model_not_available'; model: string; message: string } // Key works, model does not
+    | { status: 'unknown'; message: string };
+
+// How a crop was fitted to the provider's size and aspect-ratio limits
+export interface InputTransform {
+    original_width: number;
+    original_height: number;
+    scaled_width: number;
+    scaled_height: number;
+    padded_width: number; // Canvas actually sent to the provider
+    padded_height: number;
+    offset_x: number; // Position of the scaled crop on the canvas
+    offset_y: number;
+}
+
+// How a selection grows to the closest supported aspect ratio
+export interface AspectRatioAdjustment {
+    original_width: number;
+    original_height: number;
+    adjusted_width: number;
+    adjusted_height: number;
+    original_ratio: string;
+    closest_ratio: string;
+    needs_adjustment: boolean;
+    x: number; // Top-left of the adjusted selection; negative past the canvas edge
+    y: number;
+    pad_left: number; // Parts past the canvas edge that are padded instead of expanded into
+    pad_top: number;
+    pad_right: number;
+    pad_bottom: number;
+}
+
+// Where an API key was found; the key itself is never sent to the frontend
+export type KeySource =
+    | { kind: 'profile'; profile_id: string } // Profile named in the request
+    | { kind: 'environment'; variable: string } // e.g. BANANASLICE_API_KEY, GEMINI_API_KEY or BANANASLICE_OPENAI_API_KEY
+    | { kind: 'keyring'; profile_id: string }; // Default profile's keychain (or encrypted file) entry
+
+export interface RetryPolicy {
+    max_attempts?: number; // Total attempts including the first (default 3)
+    base_delay_ms?: number; // Doubled on every further attempt (default 1000)
+    max_delay_ms?: number; // Cap for any single delay (default 30000); a longer Retry-After fails with quota_exceeded
+    jitter?: number; // Random spread as a fraction (default 0.2)
+}
+
+export interface SafetyRating {
+    category: string; // e.g. 'HARM_CATEGORY_HARASSMENT'
+    probability: string; // e.g. 'NEGLIGIBLE', 'MEDIUM'
+    blocked: boolean; // True when this category withheld the output
+}
+
+export interface GeneratedImage {
+    index: number;
+    image_base64: string;
+    finish_reason: string | null;
+    model_text: string | null; // Text the model returned alongside the image
+    safety_ratings: SafetyRating[];
+}
+
+export type GenerationErrorKind =
+    | 'invalid_api_key'
+    | 'quota_exceeded'
+    | 'safety_blocked'
+    | 'no_image_returned'
+    | 'network'
+    | 'timeout'
+    | 'bad_request'
+    | 'credentials_locked'
+    | 'cancelled'
+    | 'unknown';
+
+export interface GenerateError {
+    kind: GenerationErrorKind;
+    message: string; // Human-readable description
+    retry_after?: number | null; // quota_exceeded: seconds to wait, if known
+    category?: string | null; // safety_blocked: finish reason or block category
+    model_text?: string | null; // no_image_returned: text the model sent instead
+    finish_reason?: string | null; // no_image_returned: why the model stopped, e.g. 'RECITATION'
+    field?: string | null; // bad_request: offending request field, if known
 }
 
 export interface GenerateResponse {
     success: boolean;
-    image_base64: string | null;
-    error: string | null;
+    image_base64: string | null; // First variant
+    images: GeneratedImage[]; // Every variant returned
+    cancelled: boolean; // True when the job was cancelled (error kind is 'cancelled')
+    error: GenerateError | null;
+    key_source: KeySource | null; // Where the API key came from
+    input_transform: InputTransform | null; // Downscaling/padding applied before upload; results are already mapped back
+    model_text: string | null; // Text the model replied with (e.g. a refusal)
+    finish_reason: string | null; // Why the model stopped, e.g. 'STOP' or 'IMAGE_SAFETY'
+    block_reason: string | null; // Set when the prompt itself was blocked
+    safety_ratings: SafetyRating[];
+}
+
+export interface GenerationJob {
+    id: string;
+    provider: string;
+    model: string;
+    started_at: number; // ms since epoch
 }
 
 export interface CompositeRequest {
@@ -40,6 +207,7 @@ export interface CompositeResponse {
  * @param imageBase64 - The cropped source image as base64
  * @param maskBase64 - The mask image as base64
  * @param referenceImages - Optional reference images to guide generation
+ * @param candidateCount - Number of variants to generate (1-4)
  */
 export async function generateFill(
     model: AIModel,
@@ -48,7 +216,8 @@ export async function generateFill(
     maskBase64: string,
     referenceImages: string[] = [],
     baseUrl: string = '',
-    customModel: string = ''
+    customModel: string = '',
+    candidateCount: number = 1
 ): Promise<GenerateResponse> {
     const request: GenerateRequest = {
         model: customModel || model,
@@ -57,11 +226,89 @@ export async function generateFill(
         mask_base64: maskBase64,
         reference_images: referenceImages,
         base_url: baseUrl || undefined,
+        candidate_count: candidateCount,
     };
 
     return invoke<GenerateResponse>('generate_fill', { request });
 }
 
+// Lifecycle events emitted as `generation://<name>`
+export type GenerationEventName =
+    'queued' | 'uploading' | 'waiting' | 'retrying' | 'decoding' | 'done' | 'failed' | 'cancelled';
+
+export interface GenerationEvent {
+    job_id: string;
+    timestamp: number; // ms since epoch
+    candidate_count?: number;
+    image_count?: number; // 'done' only
+    attempt?: number; // 'retrying' only: the attempt about to start
+    delay_ms?: number; // 'retrying' only
+    error?: string; // 'failed', or the transient failure for 'retrying'
+}
+
+const generationEventNames: GenerationEventName[] = [
+    'queued', 'uploading', 'waiting', 'retrying', 'decoding', 'done', 'failed', 'cancelled',
+];
+
+/**
+ * Subscribe to every generation lifecycle event
+ * @returns A function that removes all listeners
+ */
+export async function listenToGenerationEvents(
+    handler: (name: GenerationEventName, event: GenerationEvent) => void
+): Promise<UnlistenFn> {
+    const unlisteners = await Promise.all(
+        generationEventNames.map(name =>
+            listen<GenerationEvent>(`generation://${name}`, e => handler(name, e.payload))
+        )
+    );
+    return () => unlisteners.forEach(unlisten => unlisten());
+}
+
+/**
+ * Start a generation job and return its ID without waiting for the result
+ */
+export async function startGeneration(request: GenerateRequest): Promise<string> {
+    return invoke<string>('start_generation', { request });
+}
+
+/**
+ * Wait for a started generation job to finish
+ */
+export async function awaitGeneration(jobId: string): Promise<GenerateResponse> {
+    return invoke<GenerateResponse>('await_generation', { jobId });
+}
+
+/**
+ * Cancel a running generation job
+ */
+export async function cancelGeneration(jobId: string): Promise<boolean> {
+    return invoke<boolean>('cancel_generation', { jobId });
+}
+
+/**
+ * List generation jobs that are still running
+ */
+export async function listGenerations(): Promise<GenerationJob[]> {
+    return invoke<GenerationJob[]>('list_generations');
+}
+
+/**
+ * Work out how a selection should grow to the closest supported aspect ratio.
+ * With the canvas size, the growth stays on the canvas and the rest is reported as padding.
+ */
+export async function calculateAspectRatioAdjustment(
+    selection: { x: number; y: number; width: number; height: number },
+    canvasWidth?: number,
+    canvasHeight?: number
+): Promise<AspectRatioAdjustment> {
+    return invoke<AspectRatioAdjustment>('calculate_aspect_ratio_adjustment', {
+        selection,
+        canvasWidth: canvasWidth ?? null,
+        canvasHeight: canvasHeight ?? null,
+    });
+}
+
 /**
  * Composite a generated patch back onto the base image
  */
@@ -89,16 +336,29 @@ export async function compositePatch(
 
 // === Layer Compositing ===
 
+// Rotation, flip and skew of a layer about the centre of its box
+export interface LayerTransform {
+    rotation?: number; // Degrees, clockwise
+    flip_x?: boolean;
+    flip_y?: boolean;
+    skew_x?: number; // Degrees, in the layer's own pixels
+    skew_y?: number;
+}
+
 export interface LayerData {
     id: string;
     image_data: string;
     visible: boolean;
     opacity: number; // 0-100
-    x?: number;
+    x?: number; // Canvas pixels; may be negative or fractional
     y?: number;
     width?: number;
     height?: number;
-    blend_mode?: string;
+    blend_mode?: BlendMode;
+    transform?: LayerTransform;
+    polygon_points?: { x: number; y: number }[]; // Lasso outline, relative to x,y
+    feather_radius?: number;
+    shape_type?: 'rect' | 'ellipse';
 }
 
 export interface CompositeLayersRequest {
@@ -106,6 +366,7 @@ export interface CompositeLayersRequest {
     canvas_width: number;
     canvas_height: number;
     format: string;
+    quality?: number; // JPEG quality, 1-100
 }
 
 export interface CompositeLayersResponse {
@@ -114,6 +375,34 @@ export interface CompositeLayersResponse {
     error: string | null;
 }
 
+/**
+ * Convert a layer for compositeLayers
+ * Sends the unmasked source so the backend applies the outline and feathering itself
+ */
+export function toLayerData(layer: Layer): LayerData {
+    return {
+        id: layer.id,
+        image_data: layer.originalImageData ?? layer.imageData,
+        visible: layer.visible,
+        opacity: layer.opacity,
+        x: layer.x,
+        y: layer.y,
+        width: layer.width,
+        height: layer.height,
+        blend_mode: layer.blendMode,
+        transform: {
+            rotation: layer.rotation,
+            flip_x: layer.flipX,
+            flip_y: layer.flipY,
+            skew_x: layer.skewX,
+            skew_y: layer.skewY,
+        },
+        polygon_points: layer.polygonPoints,
+        feather_radius: layer.featherRadius,
+        shape_type: layer.shapeType,
+    };
+}
+
 /**
  * Composite all visible layers into a single image
  */
@@ -121,13 +410,15 @@ export async function compositeLayers(
     layers: LayerData[],
     canvasWidth: number,
     canvasHeight: number,
-    format: string = 'png'
+    format: string = 'png',
+    quality?: number
 ): Promise<CompositeLayersResponse> {
     const request: CompositeLayersRequest = {
         layers,
         canvas_width: canvasWidth,
         canvas_height: canvasHeight,
         format,
+        quality,
     };
 
     return invoke<CompositeLayersResponse>('composite_layers', { request });
@@ -135,18 +426,65 @@ export async function compositeLayers(
 
 /**
  * Store the API key securely
+ * With `validate`, the key is checked first and only stored if the provider
+ * accepts it (valid, quota exhausted or model not available); the check result is returned.
+ */
+export async function setApiKey(apiKey: string, validate = false): Promise<KeyValidation | null> {
+    return invoke<KeyValidation | null>('set_api_key', { apiKey, validate });
+}
+
+/**
+ * Check a key with a cheap request to its provider
+ * Pass `apiKey` to check a key before saving it; otherwise the profile's resolved key is used
  */
-export async function setApiKey(apiKey: string): Promise<void> {
-    return invoke('set_api_key', { apiKey });
+export async function validateApiKey(options: { profileId?: string; model?: string; apiKey?: string } = {}): Promise<KeyValidation> {
+    return invoke<KeyValidation>('validate_api_key', {
+        profileId: options.profileId ?? null,
+        model: options.model ?? null,
+        apiKey: options.apiKey ?? null,
+    });
 }
 
 /**
- * Check if API key is configured
+ * Check if an API key is available from a profile, the environment or the keychain
  */
 export async function hasApiKey(): Promise<boolean> {
     return invoke<boolean>('has_api_key');
 }
 
+/**
+ * Get the saved network settings (defaults when none are saved)
+ */
+export async function getNetworkSettings(): Promise<NetworkSettingsInfo> {
+    return invoke<NetworkSettingsInfo>('get_network_settings');
+}
+
+/**
+ * Save network settings; rejects settings that do not build a client (bad proxy URL or PEM)
+ * `proxyPassword` replaces the stored password when given; an empty string removes it
+ */
+export async function setNetworkSettings(settings: NetworkSettings, proxyPassword?: string): Promise<void> {
+    return invoke('set_network_settings', { settings, proxyPassword: proxyPassword ?? null });
+}
+
+/**
+ * List image models available to a profile (cached for an hour unless `refresh` is set)
+ */
+export async function listModels(options: { profileId?: string; baseUrl?: string; refresh?: boolean } = {}): Promise<ModelInfo[]> {
+    return invoke<ModelInfo[]>('list_models', {
+        profileId: options.profileId ?? null,
+        baseUrl: options.baseUrl ?? null,
+        refresh: options.refresh ?? false,
+    });
+}
+
+/**
+ * Report where the key for a profile (or the default connection) would come from
+ */
+export async function getApiKeySource(profileId?: string): Promise<KeySource | null> {
+    return invoke<KeySource | null>('get_api_key_source', { profileId: profileId ?? null });
+}
+
 /**
  * Delete the stored API key
  */
@@ -154,3 +492,65 @@ export async function deleteApiKey(): Promise<void> {
     return invoke('delete_api_key');
 }
 
+/**
+ * List credential profiles (secrets are never returned)
+ */
+export async function listProfiles(): Promise<ProfileInfo[]> {
+    return invoke<ProfileInfo[]>('list_profiles');
+}
+
+/**
+ * Add a credential profile, storing its secret in the system keychain
+ */
+export async function addProfile(request: AddProfileRequest): Promise<CredentialProfile> {
+    return invoke<CredentialProfile>('add_profile', { request });
+}
+
+/**
+ * Change a profile's label
+ */
+export async function renameProfile(profileId: string, label: string): Promise<void> {
+    return invoke('rename_profile', { profileId, label });
+}
+
+/**
+ * Delete a profile and its stored secret
+ */
+export async function deleteProfile(profileId: string): Promise<void> {
+    return invoke('delete_profile', { profileId });
+}
+
+/**
+ * Use a profile for requests that do not name one
+ */
+export async function setDefaultProfile(profileId: string): Promise<void> {
+    return invoke('set_default_profile', { profileId });
+}
+
+/**
+ * Where secrets are stored (system keychain or encrypted file)
+ */
+export async function getSecretBackend(): Promise<SecretBackendStatus> {
+    return invoke<SecretBackendStatus>('get_secret_backend');
+}
+
+/**
+ * Store secrets in a passphrase-encrypted file instead of the system keychain
+ */
+export async function enableEncryptedSecretFile(passphrase: string): Promise<SecretBackendStatus> {
+    return invoke<SecretBackendStatus>('use_encrypted_secret_file', { passphrase });
+}
+
+/**
+ * Unlock the encrypted credentials file for this session
+ */
+export async function unlockSecretFile(passphrase: string): Promise<SecretBackendStatus> {
+    return invoke<SecretBackendStatus>('unlock_secret_file', { passphrase });
+}
+
+/**
+ * Go back to the system keychain (deletes the encrypted credentials file)
+ */
+export async function switchToSystemKeychain(): Promise<SecretBackendStatus> {
+    return invoke<SecretBackendStatus>('use_system_keychain');
+}
@@ -1,7 +1,17 @@
 // BananaSlice - API Exports
-export { generateFill, compositePatch, compositeLayers, setApiKey, hasApiKey, deleteApiKey } from './generate';
+export {
+    generateFill, startGeneration, awaitGeneration, cancelGeneration, listGenerations,
+    listenToGenerationEvents,
+    calculateAspectRatioAdjustment, compositePatch, compositeLayers, toLayerData, setApiKey, validateApiKey, hasApiKey, getApiKeySource, deleteApiKey, listModels,
+    getNetworkSettings, setNetworkSettings,
+    listProfiles, addProfile, renameProfile, deleteProfile, setDefaultProfile,
+    getSecretBackend, enableEncryptedSecretFile, unlockSecretFile, switchToSystemKeychain
+} from './generate';
 export type {
-    GenerateRequest, GenerateResponse,
+    GenerateRequest, GenerateResponse, GenerateError, GenerationErrorKind, GeneratedImage, SafetyRating, GenerationJob,
+    GenerationEvent, GenerationEventName, RetryPolicy, AuthStyle,
+    CredentialProfile, ProfileInfo, AddProfileRequest, SecretBackendStatus, KeySource, KeyValidation, ModelInfo,
+    NetworkSettings, NetworkSettingsInfo, InputTransform, AspectRatioAdjustment,
     CompositeRequest, CompositeResponse,
-    LayerData, CompositeLayersRequest, CompositeLayersResponse
+    LayerData, LayerTransform, CompositeLayersRequest, CompositeLayersResponse
 } from './generate';
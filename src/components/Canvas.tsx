@@ -171,19 +171,27 @@ export function Canvas() {
             const freshTransform = useCanvasStore.getState().imageTransform;
             if (!freshTransform) return;
 
-            const relativeLeft = (obj.left - freshTransform.left) / freshTransform.scaleX;
-            const relativeTop = (obj.top - freshTransform.top) / freshTransform.scaleY;
-            const scaledWidth = obj.getScaledWidth();
-            const scaledHeight = obj.getScaledHeight();
-            const relativeWidth = scaledWidth / freshTransform.scaleX;
-            const relativeHeight = scaledHeight / freshTransform.scaleY;
-
+            // The layer box is stored untransformed; rotation, flip and skew apply about its centre
+            const center = obj.getCenterPoint();
+            const relativeWidth = Math.round((obj.width * obj.scaleX) / freshTransform.scaleX);
+            const relativeHeight = Math.round((obj.height * obj.scaleY) / freshTransform.scaleY);
+            const relativeLeft = (center.x - freshTransform.left) / freshTransform.scaleX - relativeWidth / 2;
+            const relativeTop = (center.y - freshTransform.top) / freshTransform.scaleY - relativeHeight / 2;
+
+            // Keep sub-pixel positions; the compositor resamples them on export
             useLayerStore.getState().updateLayerTransform(
                 layerId,
-                Math.round(relativeLeft),
-                Math.round(relativeTop),
-                Math.round(relativeWidth),
-                Math.round(relativeHeight)
+                relativeLeft,
+                relativeTop,
+                relativeWidth,
+                relativeHeight,
+                {
+                    rotation: obj.angle || 0,
+                    flipX: !!obj.flipX,
+                    flipY: !!obj.flipY,
+                    skewX: obj.skewX || 0,
+                    skewY: obj.skewY || 0,
+                }
             );
         };
 
@@ -2,6 +2,7 @@
 import { useState } from 'react';
 import { DragDropContext, Droppable, Draggable, type DropResult } from '@hello-pangea/dnd';
 import { useLayerStore } from '../store/layerStore';
+import type { BlendMode } from '../types';
 import { toast } from '../store/toastStore';
 import { exportLayerImage } from '../utils/exportManager';
 import { Tooltip } from './Tooltip';
@@ -11,6 +12,25 @@ interface LayerPanelProps {
     className?: string;
 }
 
+const BLEND_MODES: { value: BlendMode; label: string }[] = [
+    { value: 'normal', label: 'Normal' },
+    { value: 'multiply', label: 'Multiply' },
+    { value: 'screen', label: 'Screen' },
+    { value: 'overlay', label: 'Overlay' },
+    { value: 'soft-light', label: 'Soft Light' },
+    { value: 'hard-light', label: 'Hard Light' },
+    { value: 'darken', label: 'Darken' },
+    { value: 'lighten', label: 'Lighten' },
+    { value: 'color-dodge', label: 'Color Dodge' },
+    { value: 'color-burn', label: 'Color Burn' },
+    { value: 'difference', label: 'Difference' },
+    { value: 'exclusion', label: 'Exclusion' },
+    { value: 'hue', label: 'Hue' },
+    { value: 'saturation', label: 'Saturation' },
+    { value: 'color', label: 'Color' },
+    { value: 'luminosity', label: 'Luminosity' },
+];
+
 interface ContextMenuState {
     visible: boolean;
     x: number;
@@ -25,6 +45,7 @@ export function LayerPanel({ className = '' }: LayerPanelProps) {
         setActiveLayer,
         toggleVisibility,
         setOpacity,
+        setBlendMode,
         removeLayer,
         renameLayer,
         duplicateLayer,
@@ -261,7 +282,7 @@ export function LayerPanel({ className = '' }: LayerPanelProps) {
                                                     )}
                                                     <span className="layer-type">
                                                         {isBaseLayer(layer.type) ? 'Background' : 'Edit'}
-                                                        {layer.x !== undefined && !isBaseLayer(layer.type) && ` (${layer.x}, ${layer.y})`}
+                                                        {layer.x !== undefined && !isBaseLayer(layer.type) && ` (${Math.round(layer.x)}, ${Math.round(layer.y ?? 0)})`}
                                                     </span>
                                                 </div>
 
@@ -278,6 +299,16 @@ export function LayerPanel({ className = '' }: LayerPanelProps) {
                                                                 onClick={(e) => e.stopPropagation()}
                                                             />
                                                             <span className="opacity-value">{layer.opacity}%</span>
+                                                            <select
+                                                                className="layer-blend-mode"
+                                                                value={layer.blendMode ?? 'normal'}
+                                                                onChange={(e) => setBlendMode(layer.id, e.target.value as BlendMode)}
+                                                                onClick={(e) => e.stopPropagation()}
+                                                            >
+                                                                {BLEND_MODES.map(({ value, label }) => (
+                                                                    <option key={value} value={value}>{label}</option>
+                                                                ))}
+                                                            </select>
                                                         </div>
                                                     </Tooltip>
                                                 )}
@@ -1,6 +1,7 @@
 // Settings Modal Component
 import { useState, useEffect } from 'react';
-import { setApiKey, hasApiKey, deleteApiKey } from '../api';
+import { setApiKey, hasApiKey, deleteApiKey, getSecretBackend, enableEncryptedSecretFile, unlockSecretFile, listModels } from '../api';
+import type { SecretBackendStatus, ModelInfo } from '../api';
 import { useSettingsStore } from '../store/settingsStore';
 import { Tooltip } from './Tooltip';
 import { open } from '@tauri-apps/plugin-shell';
@@ -16,6 +17,10 @@ export function SettingsModal({ isOpen, onClose }: SettingsModalProps) {
     const [hasKey, setHasKey] = useState(false);
     const [isSaving, setIsSaving] = useState(false);
     const [message, setMessage] = useState<{ type: 'success' | 'error'; text: string } | null>(null);
+    const [backend, setBackend] = useState<SecretBackendStatus | null>(null);
+    const [passphrase, setPassphrase] = useState('');
+    const [offerFileFallback, setOfferFileFallback] = useState(false);
+    const [models, setModels] = useState<ModelInfo[]>([]);
 
     const baseUrl = useSettingsStore((s) => s.baseUrl);
     const customModel = useSettingsStore((s) => s.customModel);
@@ -31,6 +36,11 @@ export function SettingsModal({ isOpen, onClose }: SettingsModalProps) {
     const checkApiKey = async () => {
         const exists = await hasApiKey();
         setHasKey(exists);
+        setBackend(await getSecretBackend());
+        if (exists) {
+            // Suggestions for the custom model field; failures just leave it free-form
+            listModels({ baseUrl: baseUrl.trim() || undefined }).then(setModels).catch(() => setModels([]));
+        }
     };
 
     const handleSave = async () => {
@@ -41,16 +51,63 @@ export function SettingsModal({ isOpen, onClose }: SettingsModalProps) {
 
         setIsSaving(true);
         try {
-            await setApiKey(apiKey.trim());
-            setMessage({ type: 'success', text: 'API key saved successfully!' });
+            const validation = await setApiKey(apiKey.trim(), true);
+            if (validation?.status === 'invalid' || validation?.status === 'unknown') {
+                setMessage({ type: 'error', text: `API key not saved: ${validation.message}` });
+                setIsSaving(false);
+                return;
+            }
+            if (validation?.status === 'network_unreachable') {
+                // Offline: keep the key anyway rather than lose what the user typed
+                await setApiKey(apiKey.trim());
+                setMessage({ type: 'success', text: 'API key saved, but it could not be verified (network unreachable)' });
+            } else if (validation?.status === 'quota_exhausted') {
+                setMessage({ type: 'success', text: 'API key saved. Note: its quota is currently exhausted.' });
+            } else {
+                setMessage({ type: 'success', text: 'API key saved successfully!' });
+            }
             setHasKey(true);
             setApiKeyValue('');
         } catch (error) {
             setMessage({ type: 'error', text: `Failed to save: ${error}` });
+            // No usable keychain: offer the encrypted file, but never switch silently
+            if (String(error).includes('keychain')) {
+                setOfferFileFallback(true);
+            }
         }
         setIsSaving(false);
     };
 
+    const handleEnableFileBackend = async () => {
+        if (!passphrase) {
+            setMessage({ type: 'error', text: 'Please enter a passphrase' });
+            return;
+        }
+        try {
+            const status = await enableEncryptedSecretFile(passphrase);
+            setBackend(status);
+            setOfferFileFallback(false);
+            setPassphrase('');
+            setMessage({
+                type: 'success',
+                text: `Keys will be stored in an encrypted file at ${status.path}. You will need this passphrase after each restart.`,
+            });
+        } catch (error) {
+            setMessage({ type: 'error', text: `Failed to set up encrypted file: ${error}` });
+        }
+    };
+
+    const handleUnlock = async () => {
+        try {
+            setBackend(await unlockSecretFile(passphrase));
+            setPassphrase('');
+            setHasKey(await hasApiKey());
+            setMessage({ type: 'success', text: 'Credentials file unlocked' });
+        } catch (error) {
+            setMessage({ type: 'error', text: `Failed to unlock: ${error}` });
+        }
+    };
+
     const handleDelete = async () => {
         try {
             await deleteApiKey();
@@ -103,7 +160,7 @@ export function SettingsModal({ isOpen, onClose }: SettingsModalProps) {
                                         You can verify our open-source implementation{' '}
                                         <button
                                             className="link-btn"
-                                            onClick={() => handleOpenExternal('https://github.com/IrfanulM/BananaSlice/blob/main/src-tauri/src/keystore.rs')}
+                                            onClick={() => handleOpenExternal('https://github.com/IrfanulM/BananaSlice/blob/main/src-tauri/src/keystore/mod.rs')}
                                             style={{ color: 'var(--primary)', textDecoration: 'underline' }}
                                         >
                                             here
@@ -125,6 +182,44 @@ export function SettingsModal({ isOpen, onClose }: SettingsModalProps) {
                             )}
                         </div>
 
+                        {backend?.backend === 'encrypted_file' && (
+                            <div className="message" style={{ marginBottom: '12px' }}>
+                                Keys are stored in an encrypted file instead of the system keychain: {backend.path}
+                                {backend.locked && (
+                                    <div className="api-key-input-group" style={{ marginTop: '8px' }}>
+                                        <input
+                                            type="password"
+                                            placeholder="Passphrase..."
+                                            value={passphrase}
+                                            onChange={(e) => setPassphrase(e.target.value)}
+                                            className="api-key-input"
+                                        />
+                                        <button className="modal-btn primary" onClick={handleUnlock}>
+                                            Unlock
+                                        </button>
+                                    </div>
+                                )}
+                            </div>
+                        )}
+
+                        {offerFileFallback && backend?.backend === 'keychain' && (
+                            <div className="message" style={{ marginBottom: '12px' }}>
+                                No system keychain is available. You can store keys in a file encrypted with a passphrase instead.
+                                <div className="api-key-input-group" style={{ marginTop: '8px' }}>
+                                    <input
+                                        type="password"
+                                        placeholder="Choose a passphrase..."
+                                        value={passphrase}
+                                        onChange={(e) => setPassphrase(e.target.value)}
+                                        className="api-key-input"
+                                    />
+                                    <button className="modal-btn primary" onClick={handleEnableFileBackend}>
+                                        Use Encrypted File
+                                    </button>
+                                </div>
+                            </div>
+                        )}
+
                         <div className="api-key-input-group">
                             <input
                                 type="password"
@@ -183,7 +278,16 @@ export function SettingsModal({ isOpen, onClose }: SettingsModalProps) {
                             onChange={(e) => setCustomModel(e.target.value)}
                             className="api-key-input"
                             style={{ width: '100%', boxSizing: 'border-box' }}
+                            list="available-models"
                         />
+                        <datalist id="available-models">
+                            {models.map((m) => (
+                                <option key={m.id} value={m.id}>
+                                    {m.display_name}
+                                    {m.input_token_limit ? ` (${m.input_token_limit.toLocaleString()} input tokens)` : ''}
+                                </option>
+                            ))}
+                        </datalist>
                         <p className="settings-description" style={{ marginTop: '6px' }}>
                             When set, this overrides the model selector in the main panel.
                         </p>
@@ -8,7 +8,7 @@ import { useCanvasStore } from '../../store/canvasStore';
 import { useLayerStore } from '../../store/layerStore';
 import { useToolStore } from '../../store/toolStore';
 import { isSelectionTool } from '../../utils/toolHelpers';
-import { applyLayerFeathering, applySharpPolygonMask } from '../../utils/layerCompositor';
+import { applyLayerFeathering, applySharpPolygonMask, blendModeToCompositeOperation } from '../../utils/layerCompositor';
 
 interface UseLayerRendererOptions {
     fabricRef: MutableRefObject<FabricCanvas | null>;
@@ -58,6 +58,7 @@ export function useLayerRenderer({
         if (baseLayer && baseImageObjectRef.current) {
             baseImageObjectRef.current.set('visible', baseLayer.visible);
             baseImageObjectRef.current.set('opacity', baseLayer.opacity / 100);
+            baseImageObjectRef.current.set('globalCompositeOperation', blendModeToCompositeOperation(baseLayer.blendMode));
             baseImageObjectRef.current.set('borderColor', '#FFD700');
         }
 
@@ -162,12 +163,16 @@ export function useLayerRenderer({
                 const targetScaleY = targetCanvasHeight / imgHeight;
 
                 obj.set({
-                    left: targetLeft,
-                    top: targetTop,
                     scaleX: targetScaleX,
                     scaleY: targetScaleY,
+                    angle: layer.rotation ?? 0,
+                    flipX: !!layer.flipX,
+                    flipY: !!layer.flipY,
+                    skewX: layer.skewX ?? 0,
+                    skewY: layer.skewY ?? 0,
                     visible: layer.visible,
                     opacity: layer.opacity / 100,
+                    globalCompositeOperation: blendModeToCompositeOperation(layer.blendMode),
                     selectable: !isSelTool,
                     evented: !isSelTool,
                     borderColor: '#FFD700',
@@ -177,6 +182,13 @@ export function useLayerRenderer({
                     borderScaleFactor: 2,
                 });
 
+                // Position by centre so rotation, flip and skew stay within the layer box
+                obj.setPositionByOrigin(
+                    new Point(targetLeft + targetCanvasWidth / 2, targetTop + targetCanvasHeight / 2),
+                    'center',
+                    'center'
+                );
+
                 (obj as any).data = { layerId: layer.id };
                 obj.setCoords();
 
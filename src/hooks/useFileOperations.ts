@@ -177,7 +177,7 @@ export function useFileOperations({ isGenerating }: UseFileOperationsOptions) {
     const handleExport = async (format: ExportFormat) => {
         setIsExporting(true);
         try {
-            const path = await exportImage({ format, quality: 92 });
+            const path = await exportImage({ format, quality: format === 'jpeg' ? 92 : undefined });
             if (path) {
                 const fileName = path.split(/[\\/]/).pop() || 'image';
                 toast.success(`Exported as ${fileName}`);
@@ -8,9 +8,8 @@ import { useLayerStore } from '../store/layerStore';
 import { useToolStore } from '../store/toolStore';
 import { useSettingsStore } from '../store/settingsStore';
 import { toast } from '../store/toastStore';
-import { generateFill, hasApiKey } from '../api';
+import { calculateAspectRatioAdjustment, generateFill, hasApiKey } from '../api';
 import { compositeLayersInBrowser } from '../utils/layerCompositor';
-import { calculateAspectRatioAdjustment } from '../utils/aspectRatio';
 import { getSelectionBoundsCanvas } from '../utils/selectionProcessor';
 import type { ProgressStage } from '../components/ProgressIndicator';
 
@@ -115,7 +114,11 @@ export function useGeneration({ prompt, referenceImages, onOpenSettings }: UseGe
             );
 
             if (!genResult.success || !genResult.image_base64) {
-                throw new Error(genResult.error || 'Generation failed');
+                // Safety blocks often come with a model explanation worth showing
+                const explanation = genResult.error?.kind === 'safety_blocked' && genResult.model_text
+                    ? ` - ${genResult.model_text}`
+                    : '';
+                throw new Error((genResult.error?.message || 'Generation failed') + explanation);
             }
 
             // Stage 3: Apply polygon mask to result if this was a lasso selection
@@ -186,40 +189,38 @@ export function useGeneration({ prompt, referenceImages, onOpenSettings }: UseGe
         if (activeReferenceImages.length > 0) {
             const selectionBounds = getSelectionBoundsCanvas(activeSelection);
             if (selectionBounds) {
-                const adjustment = calculateAspectRatioAdjustment(
-                    selectionBounds.width,
-                    selectionBounds.height
+                // Work relative to the image as displayed so growth stays on the image
+                const originX = imageTransform?.left ?? 0;
+                const originY = imageTransform?.top ?? 0;
+                const adjustment = await calculateAspectRatioAdjustment(
+                    { ...selectionBounds, x: selectionBounds.x - originX, y: selectionBounds.y - originY },
+                    imageTransform ? baseImage.width * imageTransform.scaleX : undefined,
+                    imageTransform ? baseImage.height * imageTransform.scaleY : undefined
                 );
 
-                if (adjustment.needsAdjustment) {
+                if (adjustment.needs_adjustment) {
                     // Show confirmation dialog
                     setAspectRatioDialog({
                         open: true,
-                        originalRatio: adjustment.originalRatio,
-                        adjustedRatio: adjustment.closestRatio,
-                        widthDiff: adjustment.adjustedWidth - adjustment.originalWidth,
-                        heightDiff: adjustment.adjustedHeight - adjustment.originalHeight,
+                        originalRatio: adjustment.original_ratio,
+                        adjustedRatio: adjustment.closest_ratio,
+                        widthDiff: adjustment.adjusted_width - adjustment.original_width,
+                        heightDiff: adjustment.adjusted_height - adjustment.original_height,
                         onConfirm: () => {
                             setAspectRatioDialog(null);
 
-                            // Get current selection properties
-                            const currentWidth = activeSelection.width * (activeSelection.scaleX || 1);
-                            const currentHeight = activeSelection.height * (activeSelection.scaleY || 1);
+                            // Grow only as far as the image edge; the backend pads the rest
+                            const newWidth = adjustment.adjusted_width - adjustment.pad_left - adjustment.pad_right;
+                            const newHeight = adjustment.adjusted_height - adjustment.pad_top - adjustment.pad_bottom;
+                            const newX = Math.max(adjustment.x, 0) + originX;
+                            const newY = Math.max(adjustment.y, 0) + originY;
 
-                            // Calculate scale factors to achieve new dimensions
-                            const newScaleX = adjustment.adjustedWidth / activeSelection.width;
-                            const newScaleY = adjustment.adjustedHeight / activeSelection.height;
-
-                            // Center the expansion - adjust position
-                            const widthChange = adjustment.adjustedWidth - currentWidth;
-                            const heightChange = adjustment.adjustedHeight - currentHeight;
-
-                            // Apply the new size to the selection
+                            // Apply the new size and position to the selection
                             activeSelection.set({
-                                scaleX: newScaleX,
-                                scaleY: newScaleY,
-                                left: activeSelection.left - widthChange / 2,
-                                top: activeSelection.top - heightChange / 2,
+                                scaleX: newWidth / activeSelection.width,
+                                scaleY: newHeight / activeSelection.height,
+                                left: activeSelection.left + (newX - selectionBounds.x),
+                                top: activeSelection.top + (newY - selectionBounds.y),
                             });
                             activeSelection.setCoords();
 
@@ -231,7 +232,7 @@ export function useGeneration({ prompt, referenceImages, onOpenSettings }: UseGe
                             // Update the selection store with the modified selection
                             setActiveSelection(activeSelection);
 
-                            toast.info(`Selection adjusted to ${adjustment.closestRatio} ratio`);
+                            toast.info(`Selection adjusted to ${adjustment.closest_ratio} ratio`);
 
                             // Now proceed with generation using the resized selection
                             doGenerate();
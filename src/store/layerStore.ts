@@ -2,7 +2,7 @@
 // Manages the layer stack for compositing edits
 
 import { create } from 'zustand';
-import type { Layer } from '../types';
+import type { BlendMode, Layer } from '../types';
 
 interface LayerState {
     // Layer stack (bottom to top order)
@@ -17,9 +17,10 @@ interface LayerState {
     updateLayer: (id: string, updates: Partial<Layer>) => void;
     setActiveLayer: (id: string | null) => void;
 
-    // Visibility & opacity
+    // Visibility, opacity & blending
     toggleVisibility: (id: string) => void;
     setOpacity: (id: string, opacity: number) => void;
+    setBlendMode: (id: string, blendMode: BlendMode) => void;
 
     // Reordering
     moveLayerUp: (id: string) => void;
@@ -29,7 +30,10 @@ interface LayerState {
     // Layer editing
     renameLayer: (id: string, name: string) => void;
     duplicateLayer: (id: string) => string | null;
-    updateLayerTransform: (id: string, x: number, y: number, width: number, height: number) => void;
+    updateLayerTransform: (
+        id: string, x: number, y: number, width: number, height: number,
+        transform?: Pick<Layer, 'rotation' | 'flipX' | 'flipY' | 'skewX' | 'skewY'>
+    ) => void;
 
     // Feathering
     setFeatherRadius: (id: string, radius: number) => void;
@@ -117,6 +121,14 @@ export const useLayerStore = create<LayerState>((set, get) => ({
         }));
     },
 
+    setBlendMode: (id, blendMode) => {
+        set((state) => ({
+            layers: state.layers.map((l) =>
+                l.id === id ? { ...l, blendMode } : l
+            ),
+        }));
+    },
+
     moveLayerUp: (id) => {
         const { layers } = get();
         const index = layers.findIndex((l) => l.id === id);
@@ -188,10 +200,10 @@ export const useLayerStore = create<LayerState>((set, get) => ({
         return newId;
     },
 
-    updateLayerTransform: (id, x, y, width, height) => {
+    updateLayerTransform: (id, x, y, width, height, transform) => {
         set((state) => ({
             layers: state.layers.map((l) =>
-                l.id === id ? { ...l, x, y, width, height } : l
+                l.id === id ? { ...l, x, y, width, height, ...transform } : l
             ),
         }));
     },
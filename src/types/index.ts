@@ -40,6 +40,12 @@ export interface BoundingBox {
     height: number;
 }
 
+// Layer blend modes; the names are canvas globalCompositeOperation values
+export type BlendMode =
+    | 'normal' | 'multiply' | 'screen' | 'overlay' | 'soft-light' | 'hard-light'
+    | 'darken' | 'lighten' | 'color-dodge' | 'color-burn' | 'difference' | 'exclusion'
+    | 'hue' | 'saturation' | 'color' | 'luminosity';
+
 // Layer in the layer stack
 export interface Layer {
     id: string;
@@ -55,6 +61,12 @@ export interface Layer {
     y?: number;
     width?: number;
     height?: number;
+    // Rotation (degrees, clockwise), flip and skew (degrees) about the layer's centre
+    rotation?: number;
+    flipX?: boolean;
+    flipY?: boolean;
+    skewX?: number;
+    skewY?: number;
     // Polygon points for lasso selections (relative to layer x,y)
     polygonPoints?: { x: number; y: number }[];
     // Edge feathering radius in pixels
@@ -62,7 +74,7 @@ export interface Layer {
     // Original unmasked image for dynamic feathering
     originalImageData?: string;
     // Blend mode
-    blendMode?: 'normal' | 'multiply' | 'screen' | 'overlay';
+    blendMode?: BlendMode;
     // Shape properties
     shapeType?: 'rect' | 'ellipse';
     fillColor?: string;
@@ -1,9 +1,9 @@
-
 import { describe, it, expect, vi, beforeEach } from 'vitest';
 import { exportImage } from '../exportManager';
 import { useLayerStore } from '../../store/layerStore';
 import { useCanvasStore } from '../../store/canvasStore';
 import * as layerCompositor from '../layerCompositor';
+import { compositeLayers } from '../../api';
 import { save } from '@tauri-apps/plugin-dialog';
 import { writeFile } from '@tauri-apps/plugin-fs';
 
@@ -13,18 +13,23 @@ vi.mock('@tauri-apps/plugin-fs');
 vi.mock('../../store/layerStore');
 vi.mock('../../store/canvasStore');
 vi.mock('../layerCompositor');
+vi.mock('../../api', async (importOriginal) => ({
+    ...(await importOriginal<typeof import('../../api')>()),
+    compositeLayers: vi.fn(),
+}));
 
-describe('exportImage - Feathering Logic', () => {
-    const mockComposite = vi.spyOn(layerCompositor, 'applyLayerFeathering');
+describe('exportImage - Backend compositing', () => {
+    const mockFeathering = vi.spyOn(layerCompositor, 'applyLayerFeathering');
     const mockSharpMask = vi.spyOn(layerCompositor, 'applySharpPolygonMask');
 
     beforeEach(() => {
         vi.clearAllMocks();
-        
+
         // Setup default mocks
         (save as any).mockResolvedValue('test.png');
         (writeFile as any).mockResolvedValue(undefined);
-        
+        (compositeLayers as any).mockResolvedValue({ success: true, image_base64: 'AQID', error: null });
+
         // Mock canvas store
         (useCanvasStore.getState as any).mockReturnValue({
             baseImage: {
@@ -35,82 +40,63 @@ describe('exportImage - Feathering Logic', () => {
             },
             imagePath: 'test.banslice'
         });
-    });
 
-    it('should apply feathering when featherRadius > 0', async () => {
-        // Mock layer store with a feathered layer
+        // Mock layer store with a feathered lasso layer
         (useLayerStore.getState as any).mockReturnValue({
             layers: [
-                { type: 'base', visible: true, id: 'base' },
+                { type: 'base', visible: true, id: 'base', imageData: 'base_layer_data', opacity: 100 },
                 {
                     id: 'layer1',
-                    type: 'layer',
+                    type: 'edit',
                     visible: true,
                     opacity: 100,
+                    x: 10,
+                    y: 20,
+                    width: 30,
+                    height: 40,
                     imageData: 'raw_data',
                     originalImageData: 'original_data',
-                    featherRadius: 10,  // TRIGGER FEATHERING
-                    polygonPoints: []
+                    featherRadius: 10,
+                    polygonPoints: [{x:0, y:0}, {x:10, y:0}, {x:0, y:10}]
                 }
             ]
         });
+    });
 
-        mockComposite.mockResolvedValue('feathered_data');
-
+    it('should send unmasked layer sources to the backend compositor', async () => {
         await exportImage({ format: 'png' });
 
-        expect(mockComposite).toHaveBeenCalledTimes(1);
-        expect(mockSharpMask).not.toHaveBeenCalled();
-    });
+        expect(compositeLayers).toHaveBeenCalledTimes(1);
+        const [layers, width, height, format] = (compositeLayers as any).mock.calls[0];
+        expect([width, height, format]).toEqual([100, 100, 'png']);
 
-    it('should apply sharp mask when featherRadius is 0 but has polygon points', async () => {
-        // Mock layer store with a lasso layer (0 feather)
-        (useLayerStore.getState as any).mockReturnValue({
-            layers: [
-                { type: 'base', visible: true, id: 'base' },
-                {
-                    id: 'layer1',
-                    type: 'layer',
-                    visible: true,
-                    opacity: 100,
-                    imageData: 'raw_data',
-                    originalImageData: 'original_data',
-                    featherRadius: 0,
-                    polygonPoints: [{x:0, y:0}, {x:10, y:0}, {x:0, y:10}] // TRIGGER SHARP MASK
-                }
-            ]
+        // The base layer uses the loaded image, edit layers their original data with mask settings
+        expect(layers[0].image_data).toBe('base_image_data');
+        expect(layers[1]).toMatchObject({
+            image_data: 'original_data',
+            x: 10,
+            y: 20,
+            feather_radius: 10,
+            polygon_points: [{x:0, y:0}, {x:10, y:0}, {x:0, y:10}],
         });
 
-        mockSharpMask.mockResolvedValue('masked_data');
+        // Masking and feathering happen in the backend, not on a canvas
+        expect(mockFeathering).not.toHaveBeenCalled();
+        expect(mockSharpMask).not.toHaveBeenCalled();
+    });
 
-        await exportImage({ format: 'png' });
+    it('should write the composited image with the requested quality', async () => {
+        await exportImage({ format: 'jpeg', quality: 80 });
 
-        expect(mockSharpMask).toHaveBeenCalledTimes(1);
-        expect(mockComposite).not.toHaveBeenCalled(); // Should skip feathering
+        const [, , , format, quality] = (compositeLayers as any).mock.calls[0];
+        expect([format, quality]).toEqual(['jpeg', 80]);
+        expect(writeFile).toHaveBeenCalledWith('test.png', new Uint8Array([1, 2, 3]));
     });
 
-    it('should use originalImageData raw when no feathering and no polygon points', async () => {
-        // Mock layer store with a standard rectangle layer
-        (useLayerStore.getState as any).mockReturnValue({
-            layers: [
-                { type: 'base', visible: true, id: 'base' },
-                {
-                    id: 'layer1',
-                    type: 'layer',
-                    visible: true,
-                    opacity: 100,
-                    imageData: 'raw_data',
-                    originalImageData: 'original_data',
-                    featherRadius: 0,
-                    polygonPoints: [] // NO POLYGON
-                }
-            ]
-        });
-
-        await exportImage({ format: 'png' });
+    it('should throw when compositing fails', async () => {
+        (compositeLayers as any).mockResolvedValue({ success: false, image_base64: null, error: 'bad layer' });
 
-        // Should call neither processing function
-        expect(mockComposite).not.toHaveBeenCalled();
-        expect(mockSharpMask).not.toHaveBeenCalled();
+        await expect(exportImage({ format: 'png' })).rejects.toThrow('bad layer');
+        expect(save).not.toHaveBeenCalled();
     });
 });
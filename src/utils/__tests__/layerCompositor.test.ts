@@ -1,5 +1,5 @@
 import { describe, it, expect, vi, beforeEach } from 'vitest';
-import { compositeLayersInBrowser } from '../layerCompositor';
+import { compositeLayersInBrowser, ellipseFeatherAlpha } from '../layerCompositor';
 import type { Layer } from '../../types';
 
 describe('Layer Compositor - Integrity', () => {
@@ -61,3 +61,20 @@ describe('Layer Compositor - Integrity', () => {
         expect(call1[2]).toBe(10); // y
     });
 });
+
+describe('Layer Compositor - Ellipse feathering', () => {
+    it('should match the exporter ellipse mask', () => {
+        // Same values as ellipse_feather_matches_the_canvas_preview in compositor/mask.rs
+        const alpha = ellipseFeatherAlpha(20, 10, 2);
+        const at = (x: number, y: number) => alpha[y * 20 + x];
+
+        expect(at(0, 5)).toBe(80);
+        expect(at(1, 5)).toBe(163);
+        expect(at(2, 5)).toBe(244);
+        expect(at(3, 5)).toBe(255);
+        expect(at(10, 0)).toBe(84);
+        expect(at(10, 1)).toBe(169);
+        expect(at(3, 2)).toBe(148);
+        expect(at(0, 0)).toBe(0);
+    });
+});
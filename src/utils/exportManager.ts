@@ -5,14 +5,13 @@ import { save } from '@tauri-apps/plugin-dialog';
 import { writeFile } from '@tauri-apps/plugin-fs';
 import { useCanvasStore } from '../store/canvasStore';
 import { useLayerStore } from '../store/layerStore';
-import { applyLayerFeathering, applySharpPolygonMask } from './layerCompositor';
-import { formatToMimeType, loadImage } from './imageUtils';
+import { compositeLayers, toLayerData } from '../api';
 
 export type ExportFormat = 'png' | 'jpeg' | 'webp';
 
 interface ExportOptions {
     format: ExportFormat;
-    quality?: number; // 0-100 for jpeg/webp
+    quality?: number; // 1-100, jpeg only; png and webp are always lossless
 }
 
 /**
@@ -29,65 +28,14 @@ export const exportImage = async (options: ExportOptions): Promise<string | null
     const { baseImage } = canvasState;
     const { layers } = layerState;
 
-    // Create an offscreen canvas at the original image dimensions
-    const canvas = document.createElement('canvas');
-    canvas.width = baseImage.width;
-    canvas.height = baseImage.height;
-    const ctx = canvas.getContext('2d');
-
-    if (!ctx) {
-        throw new Error('Failed to create canvas context');
-    }
-
-
-
-    // Load and draw base image
-    const baseMimeType = formatToMimeType(baseImage.format);
-    const baseImg = await loadImage(`data:${baseMimeType};base64,${baseImage.data}`);
-    ctx.drawImage(baseImg, 0, 0);
-
-    // Draw each visible layer in order (bottom to top)
-    for (const layer of layers) {
-        if (layer.type === 'base') continue;
-        if (!layer.visible) continue;
-
-        try {
-            let finalImageData = layer.imageData;
-
-            // Use original data as the source if available for better quality/re-masking
-            if (layer.originalImageData) {
-                const featherRadius = layer.featherRadius ?? 0;
-                
-                if (featherRadius > 0) {
-                    const feathered = await applyLayerFeathering(layer);
-                    if (feathered) finalImageData = feathered;
-                } else if (layer.polygonPoints && layer.polygonPoints.length >= 3) {
-                    const masked = await applySharpPolygonMask(layer);
-                    if (masked) finalImageData = masked;
-                } else {
-                    finalImageData = layer.originalImageData;
-                }
-            }
-
-            // Robust Data URL construction
-            const dataUrl = finalImageData.startsWith('data:')
-                ? finalImageData
-                : `data:image/png;base64,${finalImageData}`;
-                
-            const layerImg = await loadImage(dataUrl);
-
-            ctx.globalAlpha = layer.opacity / 100;
-            ctx.drawImage(
-                layerImg,
-                layer.x || 0,
-                layer.y || 0,
-                layer.width || layerImg.width,
-                layer.height || layerImg.height
-            );
-            ctx.globalAlpha = 1;
-        } catch (err) {
-            console.error('Failed to draw layer:', layer.id, err);
-        }
+    // Composite in the backend, which applies masks, feathering, transforms and blend
+    // modes at full resolution from each layer's unmasked source
+    const layerData = layers.map((layer) =>
+        layer.type === 'base' ? { ...toLayerData(layer), image_data: baseImage.data } : toLayerData(layer)
+    );
+    const result = await compositeLayers(layerData, baseImage.width, baseImage.height, options.format, options.quality);
+    if (!result.success || !result.image_base64) {
+        throw new Error(result.error ?? 'Failed to composite layers');
     }
 
     // Get file extension filter based on format
@@ -124,13 +72,7 @@ export const exportImage = async (options: ExportOptions): Promise<string | null
         return null; // User cancelled
     }
 
-    // Convert canvas to the desired format
-    const mimeType = formatToMimeType(options.format);
-    const quality = options.quality !== undefined ? options.quality / 100 : 0.92;
-
-    const dataUrl = canvas.toDataURL(mimeType, quality);
-    const base64Data = dataUrl.split(',')[1];
-    const binaryData = Uint8Array.from(atob(base64Data), c => c.charCodeAt(0));
+    const binaryData = Uint8Array.from(atob(result.image_base64), c => c.charCodeAt(0));
 
     // Write to file
     await writeFile(filePath, binaryData);
@@ -1,9 +1,55 @@
 // Layer compositing utilities - runs entirely in browser
 // No backend calls needed!
 
-import type { Layer } from '../types';
+import type { BlendMode, Layer } from '../types';
 import { loadImage } from './imageUtils';
 
+/**
+ * Canvas composite operation for a layer blend mode
+ * Uses the same formulas and sRGB compositing as the Rust exporter, so previews and exports match
+ */
+export function blendModeToCompositeOperation(mode: BlendMode | undefined): GlobalCompositeOperation {
+    return !mode || mode === 'normal' ? 'source-over' : mode;
+}
+
+/**
+ * Draw a layer image into its box at (x, y), applying the layer's rotation, flip and skew
+ * about the box centre the same way the Rust compositor places layers
+ */
+export function drawLayerImage(
+    ctx: CanvasRenderingContext2D,
+    image: CanvasImageSource,
+    layer: Layer,
+    x: number,
+    y: number,
+    width: number,
+    height: number,
+    sourceWidth: number,
+    sourceHeight: number
+): void {
+    const rotation = layer.rotation ?? 0;
+    const skewX = layer.skewX ?? 0;
+    const skewY = layer.skewY ?? 0;
+    if (!rotation && !layer.flipX && !layer.flipY && !skewX && !skewY) {
+        ctx.drawImage(image, x, y, width, height);
+        return;
+    }
+
+    // Skew is measured in source pixels, so rescale it for the box
+    const scaleX = width / (sourceWidth || width);
+    const scaleY = height / (sourceHeight || height);
+    const toRadians = Math.PI / 180;
+
+    ctx.save();
+    ctx.translate(x + width / 2, y + height / 2);
+    ctx.rotate(rotation * toRadians);
+    ctx.scale(layer.flipX ? -1 : 1, layer.flipY ? -1 : 1);
+    ctx.transform(1, 0, Math.tan(skewX * toRadians) * scaleX / scaleY, 1, 0, 0);
+    ctx.transform(1, Math.tan(skewY * toRadians) * scaleY / scaleX, 0, 1, 0, 0);
+    ctx.drawImage(image, -width / 2, -height / 2, width, height);
+    ctx.restore();
+}
+
 /**
  * Create a feathered polygon mask using inset polygon and blur
  */
@@ -195,6 +241,8 @@ export async function compositeLayersInBrowser(
         const width = layer.width ?? img.width;
         const height = layer.height ?? img.height;
 
+        ctx.globalCompositeOperation = blendModeToCompositeOperation(layer.blendMode);
+
         // Check if this layer needs feathering
         const featheredMask = await createFeatheredMask(layer, width, height);
 
@@ -214,18 +262,19 @@ export async function compositeLayersInBrowser(
 
             // Draw the masked image to main canvas
             ctx.globalAlpha = layer.opacity / 100;
-            ctx.drawImage(tempCanvas, x, y);
+            drawLayerImage(ctx, tempCanvas, layer, x, y, width, height, img.width, img.height);
         } else {
             // Set opacity
             ctx.globalAlpha = layer.opacity / 100;
 
-            // Draw with position and size (handles resizing automatically)
-            ctx.drawImage(img, x, y, width, height);
+            // Draw with position, size and transform (handles resizing automatically)
+            drawLayerImage(ctx, img, layer, x, y, width, height, img.width, img.height);
         }
     }
 
-    // Reset alpha
+    // Reset alpha and blend mode
     ctx.globalAlpha = 1;
+    ctx.globalCompositeOperation = 'source-over';
 
     // Return as base64 (without the data:image/png;base64, prefix)
     const dataUrl = canvas.toDataURL('image/png');
@@ -264,6 +313,9 @@ export async function applyLayerFeathering(
             return null;
         }
         featheredMask = polyMask;
+    } else if (layer.shapeType === 'ellipse') {
+        // Ellipse shapes feather along their own outline
+        featheredMask = createEllipticalFeatheredMask(width, height, featherRadius);
     } else {
         // Use rectangular mask for rectangle selections
         featheredMask = createRectangularFeatheredMask(width, height, featherRadius);
@@ -287,6 +339,59 @@ export async function applyLayerFeathering(
     return dataUrl.split(',')[1];
 }
 
+/**
+ * Alpha (0-255) of the feathered ellipse inscribed in a width x height layer, row by row
+ * Uses the same distance estimate and ramp as the Rust exporter's ellipse mask
+ */
+export function ellipseFeatherAlpha(
+    width: number,
+    height: number,
+    featherRadius: number
+): Uint8ClampedArray {
+    const alpha = new Uint8ClampedArray(width * height);
+    const a = width / 2;
+    const b = height / 2;
+    for (let y = 0; y < height; y++) {
+        for (let x = 0; x < width; x++) {
+            const dx = x + 0.5 - a;
+            const dy = y + 0.5 - b;
+            const s = Math.hypot(dx / a, dy / b);
+            const gradient = Math.hypot(dx / (a * a), dy / (b * b));
+            // Distance inside the outline, negative outside
+            const distance = gradient < 1e-12 ? Math.min(a, b) : (1 - s) * s / gradient;
+            const coverage = Math.min(Math.max((distance + 0.5) / (featherRadius + 1), 0), 1);
+            alpha[y * width + x] = Math.round(coverage * 255);
+        }
+    }
+    return alpha;
+}
+
+/**
+ * Create an elliptical feathered mask that ramps inwards from the inscribed ellipse
+ */
+function createEllipticalFeatheredMask(
+    width: number,
+    height: number,
+    featherRadius: number
+): HTMLCanvasElement {
+    const maskCanvas = document.createElement('canvas');
+    maskCanvas.width = width;
+    maskCanvas.height = height;
+    const ctx = maskCanvas.getContext('2d')!;
+
+    const alpha = ellipseFeatherAlpha(width, height, featherRadius);
+    const imageData = ctx.createImageData(width, height);
+    for (let i = 0; i < alpha.length; i++) {
+        imageData.data[i * 4] = 255;
+        imageData.data[i * 4 + 1] = 255;
+        imageData.data[i * 4 + 2] = 255;
+        imageData.data[i * 4 + 3] = alpha[i];
+    }
+    ctx.putImageData(imageData, 0, 0);
+
+    return maskCanvas;
+}
+
 /**
  * Create a rectangular feathered mask using edge gradients
  */